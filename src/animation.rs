@@ -0,0 +1,229 @@
+//! Exporting whole beat cycles as looping animations.
+//!
+//! Frames are rendered headlessly by [`render_frame`] at evenly spaced times
+//! covering a whole number of beats, so the last frame leads seamlessly back
+//! into the first when the animation loops.
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::beat;
+use crate::paint::HeartPainter;
+use crate::render::{render_frame, RenderError, RgbaImage};
+use crate::state::AppState;
+
+/// Speed/quality trade-off used when reducing frames to a GIF palette, from
+/// 1 (best quality) to 30 (fastest).
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+/// Highest frame rate a GIF can play at. GIF delays are whole hundredths of
+/// a second, and browsers stretch delays shorter than two of them to ten.
+pub const MAX_GIF_FPS: f64 = 50.0;
+
+/// The file formats animations can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationFormat {
+    /// An animated GIF. GIF has no partial transparency, so anti-aliased
+    /// edges look best over an opaque background.
+    Gif,
+    /// An animated PNG with full alpha.
+    Apng,
+}
+
+impl AnimationFormat {
+    /// Guesses the format from a file name's extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<AnimationFormat> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        extension.parse().ok()
+    }
+}
+
+impl FromStr for AnimationFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gif" => Ok(AnimationFormat::Gif),
+            "apng" | "png" => Ok(AnimationFormat::Apng),
+            _ => Err(format!(
+                "unknown animation format `{}` (expected gif or apng)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AnimationFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnimationFormat::Gif => f.write_str("gif"),
+            AnimationFormat::Apng => f.write_str("apng"),
+        }
+    }
+}
+
+/// Describes the animation to export.
+#[derive(Clone, Debug)]
+pub struct AnimationOptions {
+    /// Width of each frame in pixels.
+    pub width: u32,
+    /// Height of each frame in pixels.
+    pub height: u32,
+    /// Frames per second. The exact rate is lowered slightly so that the
+    /// cycles contain a whole number of frames. GIFs play at most
+    /// [`MAX_GIF_FPS`].
+    pub fps: f64,
+    /// Number of beat cycles to include.
+    pub cycles: u32,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        AnimationOptions {
+            width: 256,
+            height: 256,
+            fps: 30.0,
+            cycles: 1,
+        }
+    }
+}
+
+/// A sequence of rendered frames together with its length.
+#[derive(Clone, Debug)]
+pub struct Animation {
+    /// The frames, evenly spaced in time.
+    pub frames: Vec<RgbaImage>,
+    /// The time the whole sequence takes to play, in seconds.
+    pub duration: f64,
+}
+
+impl Animation {
+    /// Renders the frames of the requested beat cycles.
+    ///
+    /// # Arguments
+    ///
+    /// * `painter` - Describes how the heart is drawn.
+    /// * `state` - The animation model; its heart rate and amplitude are used.
+    /// * `options` - The size, frame rate and length of the animation.
+    pub fn render(
+        painter: &HeartPainter,
+        state: &AppState,
+        options: &AnimationOptions,
+    ) -> Result<Animation, RenderError> {
+        let duration = options.cycles.max(1) as f64 * 60.0 / beat::clamp_bpm(state.bpm);
+        // Round down, allowing for floating point error, so the frames are
+        // never closer together than requested
+        let count = ((duration * options.fps + 1e-9).floor() as usize).max(1);
+
+        let mut state = state.clone();
+        let frames = (0..count)
+            .map(|i| {
+                state.seek(duration * i as f64 / count as f64);
+                render_frame(painter, &state, options.width, options.height)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Animation { frames, duration })
+    }
+
+    /// Writes the animation to a file in the given format, looping forever.
+    pub fn write(
+        &self,
+        format: AnimationFormat,
+        path: impl AsRef<Path>,
+    ) -> Result<(), RenderError> {
+        match format {
+            AnimationFormat::Gif => self.write_gif(path),
+            AnimationFormat::Apng => self.write_apng(path),
+        }
+    }
+
+    /// Writes the animation to an animated GIF file, looping forever.
+    ///
+    /// Fails if the frames are faster than [`MAX_GIF_FPS`], which GIF
+    /// viewers would play far too slowly.
+    pub fn write_gif(&self, path: impl AsRef<Path>) -> Result<(), RenderError> {
+        let (width, height) = self.dimensions()?;
+        let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => {
+                return Err(RenderError::Encoding(format!(
+                    "{}x{} is too large for a GIF",
+                    width, height
+                )))
+            }
+        };
+
+        // GIF delays are counted in hundredths of a second
+        let delays = self.delays(100.0);
+        if delays.iter().any(|&delay| delay < 2) {
+            return Err(RenderError::Encoding(format!(
+                "{:.1} frames per second is too fast for a GIF, which plays at most {}",
+                self.frames.len() as f64 / self.duration,
+                MAX_GIF_FPS
+            )));
+        }
+
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(file, width, height, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        for (image, delay) in self.frames.iter().zip(delays) {
+            let mut pixels = image.pixels.clone();
+            let mut frame =
+                gif::Frame::from_rgba_speed(width, height, &mut pixels, GIF_QUANTIZE_SPEED);
+            frame.delay = delay;
+            frame.dispose = gif::DisposalMethod::Background;
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the animation to an animated PNG file, looping forever.
+    pub fn write_apng(&self, path: impl AsRef<Path>) -> Result<(), RenderError> {
+        let (width, height) = self.dimensions()?;
+
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(self.frames.len() as u32, 0)?;
+
+        let mut writer = encoder.write_header()?;
+        // Delays are given in milliseconds
+        let delays = self.delays(1000.0);
+        for (image, delay) in self.frames.iter().zip(delays) {
+            writer.set_frame_delay(delay, 1000)?;
+            writer.set_dispose_op(png::DisposeOp::Background)?;
+            writer.write_image_data(&image.pixels)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Returns the size shared by all frames.
+    fn dimensions(&self) -> Result<(u32, u32), RenderError> {
+        let first = self.frames.first().ok_or(RenderError::EmptyImage)?;
+        Ok((first.width, first.height))
+    }
+
+    /// Splits the duration into per-frame delays of whole time units.
+    ///
+    /// Rounding errors are carried over from frame to frame, so the delays
+    /// always add up to the rounded duration and the loop keeps its tempo.
+    ///
+    /// # Arguments
+    ///
+    /// * `units_per_second` - The number of delay units in one second.
+    fn delays(&self, units_per_second: f64) -> Vec<u16> {
+        let count = self.frames.len();
+        let total = self.duration * units_per_second;
+        let boundary = |i: usize| (total * i as f64 / count as f64).round();
+        (0..count)
+            .map(|i| (boundary(i + 1) - boundary(i)).clamp(0.0, u16::MAX as f64) as u16)
+            .collect()
+    }
+}
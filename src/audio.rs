@@ -0,0 +1,388 @@
+//! Synthesized heart sounds.
+//!
+//! [`HeartbeatSynth`] generates the "lub" and "dub" of each beat from the
+//! same beat position and envelope that drive the heart, so the sounds land
+//! on the heart's contraction. [`render_samples`] and [`write_wav`] produce
+//! audio offline; with the `audio` feature, [`AudioPlayer`] plays it live.
+
+use std::f64::consts::{PI, TAU};
+use std::fmt;
+use std::path::Path;
+
+use crate::beat;
+use crate::envelope::{self, BeatEnvelope};
+use crate::paint::HeartPainter;
+use crate::state::AppState;
+
+/// Sample rate used unless configured otherwise, in samples per second.
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+/// Time from the first to the second heart sound, as a fraction of the beat.
+const DUB_DELAY: f64 = 0.26;
+
+/// A single heart sound: a short, low, gliding tone.
+struct Sound {
+    /// Pitch at the start of the sound, in hertz.
+    frequency: f64,
+    /// Length of the sound, in seconds.
+    duration: f64,
+    /// Peak level of the sound, from 0.0 to 1.0.
+    gain: f64,
+}
+
+/// The first heart sound, as the ventricles contract.
+const LUB: Sound = Sound {
+    frequency: 55.0,
+    duration: 0.14,
+    gain: 0.8,
+};
+
+/// The second heart sound, as the valves close.
+const DUB: Sound = Sound {
+    frequency: 75.0,
+    duration: 0.1,
+    gain: 0.55,
+};
+
+impl Sound {
+    /// Returns the sound's level `t` seconds after it started.
+    fn sample(&self, t: f64) -> f64 {
+        if !(0.0..self.duration).contains(&t) {
+            return 0.0;
+        }
+        let progress = t / self.duration;
+        // The pitch falls by a third over the sound, like a thump
+        let angle = TAU * self.frequency * (t - progress * t / 6.0);
+        let level = f64::sin(PI * progress).powi(2);
+        self.gain * level * (f64::sin(angle) + 0.3 * f64::sin(2.0 * angle))
+    }
+}
+
+/// A heart sound that is currently playing.
+struct Voice {
+    sound: &'static Sound,
+    /// Time since the sound started, in seconds.
+    t: f64,
+}
+
+/// An error raised while writing or playing audio.
+#[derive(Debug)]
+pub enum AudioError {
+    /// Writing a WAV file failed.
+    Wav(hound::Error),
+    /// No output device could be opened, or it rejected the stream.
+    Device(String),
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioError::Wav(err) => write!(f, "writing WAV failed: {}", err),
+            AudioError::Device(err) => write!(f, "audio output failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+impl From<hound::Error> for AudioError {
+    fn from(err: hound::Error) -> Self {
+        AudioError::Wav(err)
+    }
+}
+
+/// Generates heart sounds one sample at a time.
+///
+/// The synthesizer keeps its own beat position, advanced by every sample.
+/// Each sound is triggered as the position passes its point in the beat and
+/// then plays to its end, so moving the position with [`seek`] never cuts a
+/// sound off or makes it click.
+///
+/// [`seek`]: HeartbeatSynth::seek
+pub struct HeartbeatSynth {
+    sample_rate: f64,
+    /// Phase at which the first heart sound peaks.
+    lub_phase: f64,
+    beats: f64,
+    voices: Vec<Voice>,
+}
+
+impl HeartbeatSynth {
+    /// Creates a synthesizer at the start of the first beat.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The number of samples per second.
+    /// * `envelope` - The heart's beat envelope; the first heart sound peaks
+    ///   at its strongest contraction.
+    pub fn new(sample_rate: u32, envelope: &dyn BeatEnvelope) -> Self {
+        HeartbeatSynth {
+            sample_rate: sample_rate.max(1) as f64,
+            lub_phase: envelope::peak_phase(envelope),
+            beats: 0.0,
+            voices: Vec::new(),
+        }
+    }
+
+    /// Times the sounds to a different beat envelope.
+    pub fn set_envelope(&mut self, envelope: &dyn BeatEnvelope) {
+        self.lub_phase = envelope::peak_phase(envelope);
+    }
+
+    /// Returns the number of beats elapsed.
+    pub fn beats(&self) -> f64 {
+        self.beats
+    }
+
+    /// Moves to another beat position without triggering the sounds in
+    /// between; sounds already playing continue.
+    pub fn seek(&mut self, beats: f64) {
+        self.beats = beats;
+    }
+
+    /// Returns the next sample, from -1.0 to 1.0.
+    ///
+    /// # Arguments
+    ///
+    /// * `beats_per_second` - The current beat rate; zero holds the position
+    ///   while sounds already playing fade out.
+    pub fn next_sample(&mut self, beats_per_second: f64) -> f32 {
+        let next = self.beats + beats_per_second / self.sample_rate;
+        for (sound, peak) in [(&LUB, self.lub_phase), (&DUB, self.lub_phase + DUB_DELAY)] {
+            // Start early enough for the sound to be loudest at its peak
+            let onset = peak - sound.duration / 2.0 * beats_per_second;
+            if (next - onset).floor() > (self.beats - onset).floor() {
+                self.voices.push(Voice { sound, t: 0.0 });
+            }
+        }
+        self.beats = next;
+
+        let dt = 1.0 / self.sample_rate;
+        let mut value = 0.0;
+        for voice in &mut self.voices {
+            value += voice.sound.sample(voice.t);
+            voice.t += dt;
+        }
+        self.voices.retain(|voice| voice.t < voice.sound.duration);
+        value.clamp(-1.0, 1.0) as f32
+    }
+}
+
+/// Renders the heart sounds of whole beat cycles.
+///
+/// The sounds of the beat before the first cycle are played in, so the
+/// samples loop seamlessly.
+///
+/// # Arguments
+///
+/// * `painter` - Describes the heart; its envelope times the sounds.
+/// * `state` - The animation model; its heart rate and beat position are
+///   used.
+/// * `cycles` - The number of beats to render.
+/// * `sample_rate` - The number of samples per second.
+pub fn render_samples(
+    painter: &HeartPainter,
+    state: &AppState,
+    cycles: u32,
+    sample_rate: u32,
+) -> Vec<f32> {
+    let beats_per_second = beat::beats_in(1.0, beat::clamp_bpm(state.bpm));
+    let samples_per_beat = sample_rate as f64 / beats_per_second;
+    let count = (cycles.max(1) as f64 * samples_per_beat).round() as usize;
+    let lead_in = samples_per_beat.round() as usize;
+
+    let mut synth = HeartbeatSynth::new(sample_rate, &*painter.envelope);
+    synth.seek(state.beats - lead_in as f64 / samples_per_beat);
+    (0..lead_in + count)
+        .map(|_| synth.next_sample(beats_per_second))
+        .skip(lead_in)
+        .collect()
+}
+
+/// Writes samples to a mono 16-bit WAV file.
+///
+/// # Arguments
+///
+/// * `samples` - The samples, from -1.0 to 1.0.
+/// * `sample_rate` - The number of samples per second.
+/// * `path` - The file to write.
+pub fn write_wav(
+    samples: &[f32],
+    sample_rate: u32,
+    path: impl AsRef<Path>,
+) -> Result<(), AudioError> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample((sample * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+#[cfg(feature = "audio")]
+pub use player::{AudioPlayer, HeartSounds};
+
+#[cfg(feature = "audio")]
+mod player {
+    use std::sync::{Arc, Mutex};
+
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+
+    use druid::{widget::Controller, Env, Event, EventCtx, Widget};
+
+    use super::{AudioError, HeartbeatSynth};
+    use crate::beat;
+    use crate::envelope::BeatEnvelope;
+    use crate::state::AppState;
+    use crate::widget::SET_PAINTER;
+
+    /// How far, in beats, the sound may drift from the animation before it
+    /// jumps back in step.
+    const RESYNC_TOLERANCE: f64 = 0.05;
+
+    /// What the audio thread shares with the player.
+    struct Shared {
+        synth: HeartbeatSynth,
+        /// The beat rate the synthesizer advances at; zero while paused.
+        beats_per_second: f64,
+    }
+
+    /// Plays heart sounds on the default output device.
+    ///
+    /// Call [`sync`] on every animation frame so the sounds follow the
+    /// heart's rate, pauses and speed changes. The sound stops when the
+    /// player is dropped.
+    ///
+    /// [`sync`]: AudioPlayer::sync
+    pub struct AudioPlayer {
+        _stream: Stream,
+        shared: Arc<Mutex<Shared>>,
+    }
+
+    impl AudioPlayer {
+        /// Opens the default output device and starts playing.
+        ///
+        /// # Arguments
+        ///
+        /// * `state` - The animation model to start in step with.
+        /// * `envelope` - The heart's beat envelope, which times the sounds.
+        pub fn start(state: &AppState, envelope: &dyn BeatEnvelope) -> Result<Self, AudioError> {
+            let device = cpal::default_host()
+                .default_output_device()
+                .ok_or_else(|| AudioError::Device("no output device".to_string()))?;
+            let supported = device
+                .default_output_config()
+                .map_err(|err| AudioError::Device(err.to_string()))?;
+
+            let mut synth = HeartbeatSynth::new(supported.sample_rate().0, envelope);
+            synth.seek(state.beats);
+            let shared = Arc::new(Mutex::new(Shared {
+                synth,
+                beats_per_second: 0.0,
+            }));
+
+            let config = supported.config();
+            let stream = match supported.sample_format() {
+                SampleFormat::F32 => build_stream::<f32>(&device, &config, &shared),
+                SampleFormat::I16 => build_stream::<i16>(&device, &config, &shared),
+                SampleFormat::U16 => build_stream::<u16>(&device, &config, &shared),
+                format => Err(AudioError::Device(format!(
+                    "unsupported sample format {}",
+                    format
+                ))),
+            }?;
+            stream
+                .play()
+                .map_err(|err| AudioError::Device(err.to_string()))?;
+
+            let player = AudioPlayer {
+                _stream: stream,
+                shared,
+            };
+            player.sync(state);
+            Ok(player)
+        }
+
+        /// Brings the sound in step with the animation.
+        pub fn sync(&self, state: &AppState) {
+            let mut shared = self.shared.lock().unwrap();
+            shared.beats_per_second = if state.clock.is_paused() {
+                0.0
+            } else {
+                beat::beats_in(state.clock.time_scale(), state.bpm)
+            };
+            if (shared.synth.beats() - state.beats).abs() > RESYNC_TOLERANCE {
+                shared.synth.seek(state.beats);
+            }
+        }
+
+        /// Times the sounds to a different beat envelope.
+        pub fn set_envelope(&self, envelope: &dyn BeatEnvelope) {
+            self.shared.lock().unwrap().synth.set_envelope(envelope);
+        }
+    }
+
+    /// Opens an output stream filling every channel with heart sounds.
+    fn build_stream<T>(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        shared: &Arc<Mutex<Shared>>,
+    ) -> Result<Stream, AudioError>
+    where
+        T: SizedSample + FromSample<f32>,
+    {
+        let channels = config.channels.max(1) as usize;
+        let shared = Arc::clone(shared);
+        device
+            .build_output_stream(
+                config,
+                move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                    let mut shared = shared.lock().unwrap();
+                    let beats_per_second = shared.beats_per_second;
+                    for frame in data.chunks_mut(channels) {
+                        let value = T::from_sample(shared.synth.next_sample(beats_per_second));
+                        frame.fill(value);
+                    }
+                },
+                |err| eprintln!("error: audio output failed: {}", err),
+                None,
+            )
+            .map_err(|err| AudioError::Device(err.to_string()))
+    }
+
+    /// Keeps the heart sounds in step with the heart it wraps; `None` when the
+    /// sound is off.
+    pub struct HeartSounds(pub Option<AudioPlayer>);
+
+    impl<W: Widget<AppState>> Controller<AppState, W> for HeartSounds {
+        fn event(
+            &mut self,
+            child: &mut W,
+            ctx: &mut EventCtx,
+            event: &Event,
+            data: &mut AppState,
+            env: &Env,
+        ) {
+            child.event(ctx, event, data, env);
+            let Some(player) = &self.0 else {
+                return;
+            };
+            match event {
+                Event::AnimFrame(_) => player.sync(data),
+                Event::Command(cmd) => {
+                    if let Some((painter, _)) = cmd.get(SET_PAINTER) {
+                        player.set_envelope(&*painter.envelope);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Beat rate and beat position.
+//!
+//! The heart's progress through its beats is tracked as a single number of
+//! beats elapsed since the animation started. Its integer part counts complete
+//! beats and its fractional part is the phase within the current beat, so the
+//! rate can change at any time without the heart jumping to another phase.
+
+/// Heart rate used when nothing else is configured, in beats per minute.
+pub const DEFAULT_BPM: f64 = 72.0;
+
+/// Lowest heart rate accepted, in beats per minute.
+pub const MIN_BPM: f64 = 20.0;
+
+/// Highest heart rate accepted, in beats per minute.
+pub const MAX_BPM: f64 = 240.0;
+
+/// Relative size change at the peak of a beat used when nothing else is
+/// configured. An amplitude of 0.1 grows the heart by 10%.
+pub const DEFAULT_AMPLITUDE: f64 = 0.1;
+
+/// Largest beat amplitude accepted.
+pub const MAX_AMPLITUDE: f64 = 0.5;
+
+/// Returns the number of beats that fit into a span of time.
+///
+/// # Arguments
+///
+/// * `seconds` - The length of the span in seconds.
+/// * `bpm` - The heart rate in beats per minute.
+pub fn beats_in(seconds: f64, bpm: f64) -> f64 {
+    seconds * bpm / 60.0
+}
+
+/// Returns the phase within the current beat, in `[0, 1)`.
+///
+/// # Arguments
+///
+/// * `beats` - The number of beats elapsed since the animation started.
+pub fn phase(beats: f64) -> f64 {
+    beats.rem_euclid(1.0)
+}
+
+/// Clamps a heart rate to the range accepted by the animation.
+pub fn clamp_bpm(bpm: f64) -> f64 {
+    bpm.clamp(MIN_BPM, MAX_BPM)
+}
+
+/// Clamps a beat amplitude to the range accepted by the animation.
+pub fn clamp_amplitude(amplitude: f64) -> f64 {
+    amplitude.clamp(0.0, MAX_AMPLITUDE)
+}
@@ -0,0 +1,149 @@
+//! Sends a made-up heart rate to a running beating heart, for testing live
+//! input without a real sensor. It can also print Bluetooth heart-rate
+//! payloads for `beating_heart --ble-mock -` to read from a pipe.
+use std::f64::consts::TAU;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use beating_heart::{
+    beat,
+    ble::{self, HeartRateMeasurement},
+    cli::{parse_bpm, parse_non_negative, parse_positive},
+    live::{Endpoint, LiveMessage, LiveSender},
+};
+use clap::{Parser, ValueEnum};
+
+/// Interval between heart-rate messages.
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Simulate a heart-rate sensor sending to a beating heart.
+#[derive(Parser)]
+#[command(name = "fake-sensor", version)]
+struct Cli {
+    /// Where the beating heart listens, e.g. tcp://127.0.0.1:7878.
+    #[arg(required_unless_present = "ble")]
+    endpoint: Option<Endpoint>,
+    /// Print Bluetooth Heart Rate Measurement payloads as hex lines on
+    /// standard output instead of sending messages.
+    #[arg(long, conflicts_with_all = ["endpoint", "mode", "json"])]
+    ble: bool,
+    /// Average heart rate in beats per minute.
+    #[arg(long, value_parser = parse_bpm, default_value_t = beat::DEFAULT_BPM)]
+    bpm: f64,
+    /// How far the rate wanders above and below the average, in beats per
+    /// minute.
+    #[arg(long, value_parser = parse_non_negative, default_value_t = 10.0)]
+    variation: f64,
+    /// Length of one wander up and down, in seconds.
+    #[arg(long, value_parser = parse_positive, default_value_t = 30.0)]
+    period: f64,
+    /// Which messages to send.
+    #[arg(long, value_enum, default_value_t = Mode::Both)]
+    mode: Mode,
+    /// Send the JSON form of the messages instead of the text form.
+    #[arg(long)]
+    json: bool,
+}
+
+/// The kinds of messages a fake sensor sends.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Heart-rate readings once a second.
+    Rate,
+    /// A message on every beat.
+    Beats,
+    /// Both readings and beats.
+    Both,
+}
+
+impl Cli {
+    /// Returns the simulated heart rate a number of seconds in.
+    fn bpm_at(&self, seconds: f64) -> f64 {
+        let wander = f64::sin(TAU * seconds / self.period.max(1.0));
+        beat::clamp_bpm(self.bpm + self.variation * wander)
+    }
+}
+
+/// Sends messages until the connection fails.
+fn run(cli: &Cli, endpoint: &Endpoint) -> io::Result<()> {
+    let mut sender = LiveSender::connect(endpoint, cli.json)?;
+    let start = Instant::now();
+    let mut next_beat = start;
+    let mut next_rate = start;
+
+    loop {
+        let now = Instant::now();
+        let seconds = now.duration_since(start).as_secs_f64();
+        let bpm = cli.bpm_at(seconds);
+
+        if now >= next_rate {
+            if cli.mode != Mode::Beats {
+                sender.send(&LiveMessage::Bpm((bpm * 10.0).round() / 10.0))?;
+            }
+            next_rate += RATE_INTERVAL;
+        }
+        if now >= next_beat {
+            if cli.mode != Mode::Rate {
+                sender.send(&LiveMessage::Beat)?;
+            }
+            next_beat += Duration::from_secs_f64(60.0 / bpm);
+        }
+
+        thread::sleep(
+            next_beat
+                .min(next_rate)
+                .saturating_duration_since(Instant::now()),
+        );
+    }
+}
+
+/// Prints a notification payload once per notification interval, carrying
+/// the beats since the previous one, until standard output is closed.
+fn run_ble(cli: &Cli) -> io::Result<()> {
+    let start = Instant::now();
+    let mut stdout = io::stdout();
+    let mut next_notification = start + ble::NOTIFICATION_INTERVAL;
+    let mut last_beat = 0.0;
+    let mut rr_intervals = Vec::new();
+
+    loop {
+        // Collect the beats up to the next notification
+        let due = next_notification.duration_since(start).as_secs_f64();
+        loop {
+            let interval = 60.0 / cli.bpm_at(last_beat);
+            if last_beat + interval > due {
+                break;
+            }
+            last_beat += interval;
+            rr_intervals.push(interval);
+        }
+        thread::sleep(next_notification.saturating_duration_since(Instant::now()));
+
+        let measurement = HeartRateMeasurement {
+            bpm: cli.bpm_at(due).round() as u16,
+            contact: Some(true),
+            energy_expended: None,
+            rr_intervals: std::mem::take(&mut rr_intervals),
+        };
+        writeln!(stdout, "{}", ble::to_hex(&measurement.encode()))?;
+        stdout.flush()?;
+        next_notification += ble::NOTIFICATION_INTERVAL;
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match &cli.endpoint {
+        Some(endpoint) => run(&cli, endpoint).map_err(|err| format!("{}: {}", endpoint, err)),
+        None => run_ble(&cli).map_err(|err| err.to_string()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
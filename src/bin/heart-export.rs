@@ -0,0 +1,208 @@
+//! Renders the beating heart to image and sound files without opening a
+//! window.
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use beating_heart::{
+    animation::{Animation, AnimationFormat, AnimationOptions, MAX_GIF_FPS},
+    audio,
+    cli::{parse_non_negative, parse_positive, HeartArgs},
+    render,
+    svg::{self, SvgAnimation, SvgOptions},
+};
+use clap::{Args, Parser, Subcommand};
+
+/// Render the beating heart without opening a window.
+#[derive(Parser)]
+#[command(name = "heart-export", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render a single frame to a PNG file.
+    Frame(FrameArgs),
+    /// Render whole beat cycles to a looping animated GIF or APNG file.
+    Anim(AnimArgs),
+    /// Write the heart to a static or animated SVG file.
+    Svg(SvgArgs),
+    /// Synthesize the heart sounds of whole beat cycles to a WAV file.
+    Wav(WavArgs),
+}
+
+#[derive(Args)]
+struct FrameArgs {
+    /// The PNG file to write.
+    output: PathBuf,
+    /// Width of the image in pixels.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 256)]
+    width: u32,
+    /// Height of the image in pixels.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 256)]
+    height: u32,
+    /// Animation time to render, in seconds.
+    #[arg(long, value_parser = parse_non_negative, default_value_t = 0.0)]
+    time: f64,
+    #[command(flatten)]
+    heart: HeartArgs,
+}
+
+#[derive(Args)]
+struct AnimArgs {
+    /// The GIF or APNG file to write.
+    output: PathBuf,
+    /// File format; guessed from the output file's extension if omitted.
+    #[arg(long)]
+    format: Option<AnimationFormat>,
+    /// Width of the animation in pixels.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 256)]
+    width: u32,
+    /// Height of the animation in pixels.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 256)]
+    height: u32,
+    /// Frames per second; at most 50 for a GIF.
+    #[arg(long, value_parser = parse_positive, default_value_t = 30.0)]
+    fps: f64,
+    /// Number of beat cycles to include.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 1)]
+    cycles: u32,
+    #[command(flatten)]
+    heart: HeartArgs,
+}
+
+#[derive(Args)]
+struct SvgArgs {
+    /// The SVG file to write.
+    output: PathBuf,
+    /// Width of the document in pixels.
+    #[arg(long, value_parser = parse_positive, default_value_t = 256.0)]
+    width: f64,
+    /// Height of the document in pixels.
+    #[arg(long, value_parser = parse_positive, default_value_t = 256.0)]
+    height: f64,
+    /// Animation time to draw, in seconds; ignored with --animate.
+    #[arg(long, value_parser = parse_non_negative, default_value_t = 0.0)]
+    time: f64,
+    /// Make the heart beat using SMIL (`smil`) or CSS (`css`) animation.
+    #[arg(long)]
+    animate: Option<SvgAnimation>,
+    /// Number of keyframes per beat in an animated SVG.
+    #[arg(long, default_value_t = svg::DEFAULT_SAMPLES_PER_BEAT)]
+    samples: usize,
+    #[command(flatten)]
+    heart: HeartArgs,
+}
+
+#[derive(Args)]
+struct WavArgs {
+    /// The WAV file to write.
+    output: PathBuf,
+    /// Samples per second.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(8000..), default_value_t = audio::DEFAULT_SAMPLE_RATE)]
+    sample_rate: u32,
+    /// Number of beat cycles to include.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 1)]
+    cycles: u32,
+    #[command(flatten)]
+    heart: HeartArgs,
+}
+
+/// Renders a single frame as requested on the command line.
+fn frame(args: &FrameArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let builder = args.heart.builder();
+    render::render_png(
+        &builder.painter(),
+        &builder.state(),
+        args.time,
+        args.width,
+        args.height,
+        &args.output,
+    )?;
+    Ok(())
+}
+
+/// Renders an animation as requested on the command line.
+fn anim(args: &AnimArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let format = match args.format {
+        Some(format) => format,
+        None => AnimationFormat::from_path(&args.output).ok_or_else(|| {
+            format!(
+                "cannot tell the format of `{}`; use --format",
+                args.output.display()
+            )
+        })?,
+    };
+    // Fail before rendering frames the GIF could not play
+    if format == AnimationFormat::Gif && args.fps > MAX_GIF_FPS {
+        return Err(format!(
+            "--fps {} is too fast for a GIF, which plays at most {} frames per second",
+            args.fps, MAX_GIF_FPS
+        )
+        .into());
+    }
+    let builder = args.heart.builder();
+    let options = AnimationOptions {
+        width: args.width,
+        height: args.height,
+        fps: args.fps,
+        cycles: args.cycles,
+    };
+    let animation = Animation::render(&builder.painter(), &builder.state(), &options)?;
+    animation.write(format, &args.output)?;
+    Ok(())
+}
+
+/// Writes an SVG file as requested on the command line.
+fn svg(args: &SvgArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let builder = args.heart.builder();
+    let painter = builder.painter();
+    let mut state = builder.state();
+    let options = SvgOptions {
+        width: args.width,
+        height: args.height,
+    };
+
+    let document = match args.animate {
+        Some(animation) => svg::animated_svg(&painter, &state, &options, animation, args.samples),
+        None => {
+            state.seek(args.time);
+            svg::static_svg(&painter, &state, &options)
+        }
+    };
+    std::fs::write(&args.output, document)?;
+    Ok(())
+}
+
+/// Writes a WAV file as requested on the command line.
+fn wav(args: &WavArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let builder = args.heart.builder();
+    let samples = audio::render_samples(
+        &builder.painter(),
+        &builder.state(),
+        args.cycles,
+        args.sample_rate,
+    );
+    audio::write_wav(&samples, args.sample_rate, &args.output)?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match &cli.command {
+        Command::Frame(args) => frame(args),
+        Command::Anim(args) => anim(args),
+        Command::Svg(args) => svg(args),
+        Command::Wav(args) => wav(args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
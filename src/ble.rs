@@ -0,0 +1,456 @@
+//! Heart rate from Bluetooth Low Energy sensors.
+//!
+//! Chest straps and watches implementing the Bluetooth Heart Rate Profile
+//! notify their readings as Heart Rate Measurement characteristic values.
+//! [`HeartRateMeasurement`] decodes those payloads and turns them into
+//! [`LiveMessage`]s, the same messages live socket input produces.
+//!
+//! Payloads arrive through a [`HeartRateTransport`]. [`MockTransport`] reads
+//! them as hex lines from a file or pipe, so everything above the radio can
+//! be exercised without Bluetooth hardware.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::live::LiveMessage;
+
+/// Flag bit: the heart rate is a 16-bit value instead of an 8-bit one.
+const FLAG_HR_16BIT: u8 = 0x01;
+/// Flag bit: the sensor reports whether it touches the skin.
+const FLAG_CONTACT_SUPPORTED: u8 = 0x04;
+/// Flag bit: the sensor touches the skin.
+const FLAG_CONTACT_DETECTED: u8 = 0x02;
+/// Flag bit: the energy expended field is present.
+const FLAG_ENERGY: u8 = 0x08;
+/// Flag bit: one or more RR intervals are present.
+const FLAG_RR: u8 = 0x10;
+
+/// Resolution of RR intervals: 1/1024 of a second.
+const RR_UNITS_PER_SECOND: f64 = 1024.0;
+
+/// Time between notifications of a typical sensor.
+pub const NOTIFICATION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An error raised while decoding a Heart Rate Measurement payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The payload has no flags byte.
+    Empty,
+    /// The payload ends before a field the flags announce.
+    Truncated(&'static str),
+    /// The RR interval field does not consist of whole 16-bit values.
+    PartialRrInterval,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Empty => f.write_str("the payload is empty"),
+            DecodeError::Truncated(field) => write!(f, "the payload ends inside the {}", field),
+            DecodeError::PartialRrInterval => f.write_str("the payload ends inside an RR interval"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// A decoded Heart Rate Measurement characteristic value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeartRateMeasurement {
+    /// Heart rate in beats per minute.
+    pub bpm: u16,
+    /// Whether the sensor touches the skin; `None` if it cannot tell.
+    pub contact: Option<bool>,
+    /// Energy expended since the last reset, in kilojoules.
+    pub energy_expended: Option<u16>,
+    /// Times between consecutive beats since the previous notification,
+    /// oldest first, in seconds.
+    pub rr_intervals: Vec<f64>,
+}
+
+impl HeartRateMeasurement {
+    /// Decodes a payload as notified by a sensor.
+    pub fn decode(payload: &[u8]) -> Result<HeartRateMeasurement, DecodeError> {
+        let (&flags, mut rest) = payload.split_first().ok_or(DecodeError::Empty)?;
+
+        let bpm = if flags & FLAG_HR_16BIT != 0 {
+            read_u16(&mut rest).ok_or(DecodeError::Truncated("heart rate"))?
+        } else {
+            let (&bpm, tail) = rest
+                .split_first()
+                .ok_or(DecodeError::Truncated("heart rate"))?;
+            rest = tail;
+            bpm as u16
+        };
+
+        let contact =
+            (flags & FLAG_CONTACT_SUPPORTED != 0).then_some(flags & FLAG_CONTACT_DETECTED != 0);
+
+        let energy_expended = if flags & FLAG_ENERGY != 0 {
+            Some(read_u16(&mut rest).ok_or(DecodeError::Truncated("energy expended"))?)
+        } else {
+            None
+        };
+
+        let mut rr_intervals = Vec::new();
+        if flags & FLAG_RR != 0 {
+            if !rest.len().is_multiple_of(2) {
+                return Err(DecodeError::PartialRrInterval);
+            }
+            while let Some(rr) = read_u16(&mut rest) {
+                rr_intervals.push(rr as f64 / RR_UNITS_PER_SECOND);
+            }
+        }
+
+        Ok(HeartRateMeasurement {
+            bpm,
+            contact,
+            energy_expended,
+            rr_intervals,
+        })
+    }
+
+    /// Encodes the measurement as a sensor would notify it.
+    ///
+    /// RR intervals are rounded to the payload's resolution.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut flags = 0;
+        let mut payload = vec![0];
+        match u8::try_from(self.bpm) {
+            Ok(bpm) => payload.push(bpm),
+            Err(_) => {
+                flags |= FLAG_HR_16BIT;
+                payload.extend(self.bpm.to_le_bytes());
+            }
+        }
+        if let Some(contact) = self.contact {
+            flags |= FLAG_CONTACT_SUPPORTED;
+            if contact {
+                flags |= FLAG_CONTACT_DETECTED;
+            }
+        }
+        if let Some(energy) = self.energy_expended {
+            flags |= FLAG_ENERGY;
+            payload.extend(energy.to_le_bytes());
+        }
+        if !self.rr_intervals.is_empty() {
+            flags |= FLAG_RR;
+            for rr in &self.rr_intervals {
+                let units = (rr * RR_UNITS_PER_SECOND)
+                    .round()
+                    .clamp(0.0, u16::MAX as f64);
+                payload.extend((units as u16).to_le_bytes());
+            }
+        }
+        payload[0] = flags;
+        payload
+    }
+
+    /// Returns the messages this measurement stands for.
+    ///
+    /// Without skin contact the reading is meaningless and nothing is
+    /// returned. RR intervals give the most precise rate, so their average
+    /// is preferred over the rounded heart rate. The newest RR interval ends
+    /// with a beat shortly before the notification, which is reported as a
+    /// beat happening now.
+    pub fn messages(&self) -> Vec<LiveMessage> {
+        if self.contact == Some(false) {
+            return Vec::new();
+        }
+        let mut messages = Vec::new();
+        if self.rr_intervals.is_empty() {
+            if self.bpm > 0 {
+                messages.push(LiveMessage::Bpm(self.bpm as f64));
+            }
+        } else {
+            let total: f64 = self.rr_intervals.iter().sum();
+            if total > 0.0 {
+                let mean = total / self.rr_intervals.len() as f64;
+                messages.push(LiveMessage::Bpm(60.0 / mean));
+            }
+            messages.push(LiveMessage::Beat);
+        }
+        messages
+    }
+}
+
+/// A source of Heart Rate Measurement notifications.
+pub trait HeartRateTransport {
+    /// Waits for the next notification and returns its payload, or `None`
+    /// once the sensor has disconnected.
+    fn next_payload(&mut self) -> io::Result<Option<Vec<u8>>>;
+}
+
+/// A transport replaying payloads written as hex lines.
+///
+/// Each line holds one payload, such as `16 48 00 04` or `16480004`; blank
+/// lines and lines starting with `#` are skipped. The lines can come from a
+/// file, a named pipe or standard input.
+pub struct MockTransport<R> {
+    lines: io::Lines<R>,
+    interval: Option<Duration>,
+    started: bool,
+}
+
+impl<R: BufRead> MockTransport<R> {
+    /// Creates a transport reading lines as fast as they are available.
+    pub fn new(reader: R) -> Self {
+        MockTransport {
+            lines: reader.lines(),
+            interval: None,
+            started: false,
+        }
+    }
+
+    /// Waits `interval` between payloads, as a sensor would.
+    pub fn paced(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+}
+
+impl MockTransport<BufReader<Box<dyn Read + Send>>> {
+    /// Opens a file, or standard input for `-`.
+    ///
+    /// Regular files are replayed at [`NOTIFICATION_INTERVAL`]; pipes and
+    /// standard input deliver payloads as they are written.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if path == Path::new("-") {
+            let stdin: Box<dyn Read + Send> = Box::new(io::stdin());
+            return Ok(MockTransport::new(BufReader::new(stdin)));
+        }
+        let file = File::open(path)?;
+        let regular = file.metadata()?.is_file();
+        let file: Box<dyn Read + Send> = Box::new(file);
+        let transport = MockTransport::new(BufReader::new(file));
+        Ok(if regular {
+            transport.paced(NOTIFICATION_INTERVAL)
+        } else {
+            transport
+        })
+    }
+}
+
+impl<R: BufRead> HeartRateTransport for MockTransport<R> {
+    fn next_payload(&mut self) -> io::Result<Option<Vec<u8>>> {
+        for line in self.lines.by_ref() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let payload = parse_hex(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("`{}` is not a hex payload", line),
+                )
+            })?;
+            if let (Some(interval), true) = (self.interval, self.started) {
+                thread::sleep(interval);
+            }
+            self.started = true;
+            return Ok(Some(payload));
+        }
+        Ok(None)
+    }
+}
+
+/// Decodes the notifications of a transport on a background thread.
+///
+/// `on_message` receives the messages each measurement stands for, and
+/// descriptions of payloads that could not be decoded. It returns `false`
+/// to stop. The thread ends when the transport disconnects or fails.
+///
+/// # Arguments
+///
+/// * `transport` - The source of notifications.
+/// * `on_message` - Called with every decoded message.
+pub fn spawn<T, F>(mut transport: T, mut on_message: F) -> thread::JoinHandle<()>
+where
+    T: HeartRateTransport + Send + 'static,
+    F: FnMut(Result<LiveMessage, String>) -> bool + Send + 'static,
+{
+    thread::spawn(move || loop {
+        let payload = match transport.next_payload() {
+            Ok(Some(payload)) => payload,
+            Ok(None) => break,
+            Err(err) => {
+                on_message(Err(format!("the sensor failed: {}", err)));
+                break;
+            }
+        };
+        let results = match HeartRateMeasurement::decode(&payload) {
+            Ok(measurement) => measurement.messages().into_iter().map(Ok).collect(),
+            Err(err) => vec![Err(format!("bad heart rate measurement: {}", err))],
+        };
+        for result in results {
+            if !on_message(result) {
+                return;
+            }
+        }
+    })
+}
+
+/// Formats a payload as a hex line that [`MockTransport`] reads.
+pub fn to_hex(payload: &[u8]) -> String {
+    let bytes: Vec<String> = payload.iter().map(|byte| format!("{:02x}", byte)).collect();
+    bytes.join(" ")
+}
+
+/// Parses hex digits into bytes, ignoring spaces and colons between them.
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace() && *byte != b':')
+        .collect();
+    // Also rules out the sign `from_str_radix` would accept
+    if !digits.len().is_multiple_of(2) || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Reads a little-endian 16-bit value from the front of a slice.
+fn read_u16(bytes: &mut &[u8]) -> Option<u16> {
+    let (value, rest) = bytes.split_first_chunk::<2>()?;
+    *bytes = rest;
+    Some(u16::from_le_bytes(*value))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn decodes_8_and_16_bit_rates() {
+        let measurement = HeartRateMeasurement::decode(&[0x00, 72]).unwrap();
+        assert_eq!(
+            measurement,
+            HeartRateMeasurement {
+                bpm: 72,
+                ..Default::default()
+            }
+        );
+        let measurement = HeartRateMeasurement::decode(&[0x01, 0x2c, 0x01]).unwrap();
+        assert_eq!(measurement.bpm, 300);
+    }
+
+    #[test]
+    fn decodes_sensor_contact() {
+        let contact = |flags| HeartRateMeasurement::decode(&[flags, 60]).unwrap().contact;
+        assert_eq!(contact(0x00), None);
+        // Detected contact means nothing unless contact is supported
+        assert_eq!(contact(0x02), None);
+        assert_eq!(contact(0x04), Some(false));
+        assert_eq!(contact(0x06), Some(true));
+    }
+
+    #[test]
+    fn decodes_energy_and_rr_intervals() {
+        let payload = [0x18, 60, 0x10, 0x00, 0x00, 0x04, 0x00, 0x03];
+        let measurement = HeartRateMeasurement::decode(&payload).unwrap();
+        assert_eq!(measurement.energy_expended, Some(16));
+        assert_eq!(measurement.rr_intervals, vec![1.0, 0.75]);
+    }
+
+    #[test]
+    fn rejects_malformed_payloads() {
+        let decode = HeartRateMeasurement::decode;
+        assert_eq!(decode(&[]), Err(DecodeError::Empty));
+        assert_eq!(decode(&[0x00]), Err(DecodeError::Truncated("heart rate")));
+        assert_eq!(
+            decode(&[0x01, 0x48]),
+            Err(DecodeError::Truncated("heart rate"))
+        );
+        assert_eq!(
+            decode(&[0x08, 60, 0x10]),
+            Err(DecodeError::Truncated("energy expended"))
+        );
+        assert_eq!(
+            decode(&[0x10, 60, 0x00, 0x04, 0x00]),
+            Err(DecodeError::PartialRrInterval)
+        );
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        let measurements = [
+            HeartRateMeasurement {
+                bpm: 72,
+                ..Default::default()
+            },
+            HeartRateMeasurement {
+                bpm: 300,
+                contact: Some(true),
+                energy_expended: Some(1234),
+                rr_intervals: vec![0.5, 0.75, 1.0],
+            },
+            HeartRateMeasurement {
+                bpm: 0,
+                contact: Some(false),
+                energy_expended: None,
+                rr_intervals: Vec::new(),
+            },
+        ];
+        for measurement in measurements {
+            let payload = measurement.encode();
+            assert_eq!(HeartRateMeasurement::decode(&payload), Ok(measurement));
+        }
+    }
+
+    #[test]
+    fn messages_follow_rr_intervals_and_contact() {
+        let measurement = HeartRateMeasurement {
+            bpm: 70,
+            contact: Some(true),
+            energy_expended: None,
+            rr_intervals: vec![0.75, 1.25],
+        };
+        assert_eq!(
+            measurement.messages(),
+            vec![LiveMessage::Bpm(60.0), LiveMessage::Beat]
+        );
+
+        let lost = HeartRateMeasurement {
+            contact: Some(false),
+            ..measurement
+        };
+        assert!(lost.messages().is_empty());
+    }
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!(parse_hex("16 48 00 04"), Some(vec![0x16, 0x48, 0x00, 0x04]));
+        assert_eq!(parse_hex("16:48:00:04"), Some(vec![0x16, 0x48, 0x00, 0x04]));
+        assert_eq!(parse_hex("16480004"), Some(vec![0x16, 0x48, 0x00, 0x04]));
+        assert_eq!(parse_hex("164"), None);
+        assert_eq!(parse_hex("zz"), None);
+        assert_eq!(parse_hex("+1"), None);
+        assert_eq!(
+            parse_hex(&to_hex(&[0x00, 0xff, 0x10])),
+            Some(vec![0x00, 0xff, 0x10])
+        );
+    }
+
+    #[test]
+    fn mock_transport_skips_comments() {
+        let lines = "# recorded strap\n\n06 48\n16 48 00 04\n";
+        let mut transport = MockTransport::new(Cursor::new(lines));
+        assert_eq!(transport.next_payload().unwrap(), Some(vec![0x06, 0x48]));
+        assert_eq!(
+            transport.next_payload().unwrap(),
+            Some(vec![0x16, 0x48, 0x00, 0x04])
+        );
+        assert_eq!(transport.next_payload().unwrap(), None);
+    }
+}
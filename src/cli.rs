@@ -0,0 +1,241 @@
+//! Command-line options shared by the bundled binaries.
+//!
+//! Every option is validated while parsing, so a bad value is reported with
+//! a message naming the option instead of surfacing later as a panic.
+
+use clap::Args;
+use druid::{
+    piet::{LineCap, LineJoin},
+    Color, Size,
+};
+
+use crate::beat;
+use crate::color::ColorRamp;
+use crate::envelope::EnvelopeProfile;
+use crate::paint::{HeartPainter, PaintOrder, StrokeAlign, StrokeOptions};
+use crate::shapes::Icon;
+use crate::style::{Gradient, HeartStyle};
+use crate::theme::Theme;
+use crate::widget::{HeartBuilder, HeartWidget};
+
+pub use crate::color::parse_color;
+
+/// Options describing the look and beat of a heart.
+#[derive(Args, Clone, Debug)]
+pub struct HeartArgs {
+    /// Heart rate in beats per minute.
+    #[arg(long, value_parser = parse_bpm, default_value_t = beat::DEFAULT_BPM)]
+    pub bpm: f64,
+    /// Relative size change at the peak of a beat, e.g. 0.1 for 10%.
+    #[arg(long, value_parser = parse_amplitude, default_value_t = beat::DEFAULT_AMPLITUDE)]
+    pub amplitude: f64,
+    /// Envelope shaping each beat: lub-dub, systolic or sine.
+    #[arg(long, default_value_t = EnvelopeProfile::default())]
+    pub envelope: EnvelopeProfile,
+    /// Shape to pulse with: heart, outline, rounded or anatomical.
+    #[arg(long)]
+    pub shape: Option<Icon>,
+    /// SVG path data of a custom shape to pulse with instead, e.g.
+    /// `M0 0 L10 0 L5 8 Z`.
+    #[arg(long, value_name = "PATH", value_parser = Icon::from_svg, conflicts_with = "shape")]
+    pub shape_path: Option<Icon>,
+    /// Color theme: light, dark or high-contrast. Colors given explicitly
+    /// take precedence over the theme's.
+    #[arg(long)]
+    pub theme: Option<Theme>,
+    /// Fill color, as `#rrggbb` or `#rrggbbaa`; red by default.
+    #[arg(long, value_parser = parse_color)]
+    pub fill: Option<Color>,
+    /// Colors the fill passes through over each beat: `flush` or
+    /// `flush:DEPTH` to darken it at rest and brighten it at the peak, or
+    /// comma-separated colors from rest to peak, e.g. `#800000,#ff4060`.
+    #[arg(long)]
+    pub color_ramp: Option<ColorRamp>,
+    /// Outline color, as `#rrggbb` or `#rrggbbaa`; black by default.
+    #[arg(long, value_parser = parse_color)]
+    pub stroke: Option<Color>,
+    /// Outline width in pixels; 0 disables the outline. 4 by default.
+    #[arg(long, value_parser = parse_stroke_width)]
+    pub stroke_width: Option<f64>,
+    /// Where the outline lies: centered on the edge, inside or outside.
+    #[arg(long, default_value_t = StrokeAlign::default())]
+    pub stroke_align: StrokeAlign,
+    /// How the outline turns corners: miter, round or bevel.
+    #[arg(long, value_parser = parse_line_join, default_value = "miter")]
+    pub line_join: LineJoin,
+    /// How the ends of dashes are drawn: butt, round or square.
+    #[arg(long, value_parser = parse_line_cap, default_value = "butt")]
+    pub line_cap: LineCap,
+    /// Dash pattern of the outline, as comma-separated lengths of dashes
+    /// and gaps in pixels, e.g. `8,4`; the outline is solid if omitted.
+    // The path is spelled out so clap parses the whole pattern at once
+    // instead of one length at a time
+    #[arg(long, value_parser = parse_dash)]
+    pub dash: Option<::std::vec::Vec<f64>>,
+    /// Order in which the heart is painted: fill-first or stroke-first.
+    #[arg(long, default_value_t = PaintOrder::default())]
+    pub paint_order: PaintOrder,
+    /// Background color; the background is transparent if omitted.
+    #[arg(long, value_parser = parse_color)]
+    pub background: Option<Color>,
+    /// Fill shading: flat, radial or linear.
+    #[arg(long, default_value_t = Gradient::default())]
+    pub gradient: Gradient,
+    /// Cast a soft shadow beneath the heart.
+    #[arg(long)]
+    pub shadow: bool,
+    /// Surround the heart with a glow that intensifies at systole.
+    #[arg(long)]
+    pub glow: bool,
+    /// Add a glossy highlight to the heart.
+    #[arg(long)]
+    pub gloss: bool,
+}
+
+impl HeartArgs {
+    /// Returns a builder configured with these options.
+    pub fn builder(&self) -> HeartBuilder {
+        let mut builder = HeartWidget::builder()
+            .bpm(self.bpm)
+            .amplitude(self.amplitude)
+            .envelope(self.envelope)
+            .stroke_options(StrokeOptions {
+                align: self.stroke_align,
+                join: self.line_join,
+                cap: self.line_cap,
+                dash: self.dash.clone().unwrap_or_default(),
+                dash_offset: 0.0,
+            })
+            .paint_order(self.paint_order)
+            .style(HeartStyle {
+                gradient: self.gradient,
+                shadow: self.shadow,
+                glow: self.glow,
+                gloss: self.gloss,
+            });
+        if let Some(shape) = self.shape_path.as_ref().or(self.shape.as_ref()) {
+            builder = builder.shape(shape.clone());
+        }
+        if let Some(fill) = self.fill {
+            builder = builder.fill(fill);
+        }
+        if let Some(ramp) = &self.color_ramp {
+            builder = builder.color_ramp(ramp.clone());
+        }
+        if let Some(stroke) = self.stroke {
+            builder = builder.stroke(stroke);
+        }
+        if let Some(width) = self.stroke_width {
+            builder = builder.stroke_width(width);
+        }
+        if let Some(background) = self.background {
+            builder = builder.background(background);
+        }
+        match self.theme {
+            Some(theme) => builder.theme(theme),
+            None => builder,
+        }
+    }
+
+    /// Returns a painter drawing the heart these options describe.
+    pub fn painter(&self) -> HeartPainter {
+        self.builder().painter()
+    }
+}
+
+/// Parses a line join: `miter`, `round` or `bevel`.
+pub fn parse_line_join(s: &str) -> Result<LineJoin, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "miter" => Ok(LineJoin::default()),
+        "round" => Ok(LineJoin::Round),
+        "bevel" => Ok(LineJoin::Bevel),
+        _ => Err(format!(
+            "unknown line join `{}` (expected miter, round or bevel)",
+            s
+        )),
+    }
+}
+
+/// Parses a line cap: `butt`, `round` or `square`.
+pub fn parse_line_cap(s: &str) -> Result<LineCap, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "butt" => Ok(LineCap::Butt),
+        "round" => Ok(LineCap::Round),
+        "square" => Ok(LineCap::Square),
+        _ => Err(format!(
+            "unknown line cap `{}` (expected butt, round or square)",
+            s
+        )),
+    }
+}
+
+/// Parses a size given as `WIDTHxHEIGHT`, such as `400x300`.
+pub fn parse_size(s: &str) -> Result<Size, String> {
+    let (width, height) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("`{}` is not a size; expected WIDTHxHEIGHT", s))?;
+    Ok(Size::new(parse_positive(width)?, parse_positive(height)?))
+}
+
+/// Parses a finite number greater than zero.
+pub fn parse_positive(s: &str) -> Result<f64, String> {
+    match parse_number(s)? {
+        value if value > 0.0 => Ok(value),
+        _ => Err(format!("`{}` must be greater than zero", s)),
+    }
+}
+
+/// Parses a heart rate within the range the animation accepts.
+pub fn parse_bpm(s: &str) -> Result<f64, String> {
+    parse_in_range(s, beat::MIN_BPM, beat::MAX_BPM)
+}
+
+/// Parses a beat amplitude within the range the animation accepts.
+fn parse_amplitude(s: &str) -> Result<f64, String> {
+    parse_in_range(s, 0.0, beat::MAX_AMPLITUDE)
+}
+
+/// Parses an outline width, which may be zero but not negative.
+fn parse_stroke_width(s: &str) -> Result<f64, String> {
+    parse_non_negative(s)
+}
+
+/// Parses a dash pattern as comma-separated lengths of dashes and gaps,
+/// which may be zero but not negative or all zero.
+fn parse_dash(s: &str) -> Result<Vec<f64>, String> {
+    let lengths = s
+        .split(',')
+        .map(parse_non_negative)
+        .collect::<Result<Vec<_>, _>>()?;
+    if lengths.iter().sum::<f64>() > 0.0 {
+        Ok(lengths)
+    } else {
+        Err(format!("`{}` must have a dash or gap longer than zero", s))
+    }
+}
+
+/// Parses a finite number that is not negative.
+pub fn parse_non_negative(s: &str) -> Result<f64, String> {
+    match parse_number(s)? {
+        value if value >= 0.0 => Ok(value),
+        _ => Err(format!("`{}` must not be negative", s)),
+    }
+}
+
+/// Parses a finite number between `min` and `max`, inclusive.
+pub fn parse_in_range(s: &str, min: f64, max: f64) -> Result<f64, String> {
+    let value = parse_number(s)?;
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("`{}` must be between {} and {}", s, min, max))
+    }
+}
+
+/// Parses a finite number.
+fn parse_number(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(format!("`{}` is not a number", s)),
+    }
+}
@@ -0,0 +1,292 @@
+//! Animation clock driving the beating heart.
+//!
+//! druid reports the real time elapsed between two animation frames in
+//! nanoseconds through `Event::AnimFrame`. The clock converts those intervals
+//! into animation time, taking pausing, time scaling and an optional fixed
+//! step into account, so the beat rate does not depend on the refresh rate of
+//! the display.
+//!
+//! [`ClockKeys`] lets the keyboard pause, speed up and seek the clock of the
+//! heart it wraps.
+
+use druid::{
+    widget::Controller, Data, Env, Event, EventCtx, KbKey, LifeCycle, LifeCycleCtx, Widget,
+};
+
+use crate::state::AppState;
+
+/// Step length used by fixed-step mode unless told otherwise: one 60 Hz frame.
+pub const DEFAULT_FIXED_STEP: f64 = 1.0 / 60.0;
+
+/// Factor by which [`ClockKeys`] speeds up or slows down the animation.
+pub const TIME_SCALE_STEP: f64 = 1.25;
+
+/// Seconds [`ClockKeys`] skips through a timeline.
+pub const SEEK_STEP: f64 = 5.0;
+
+/// Longest real interval, in seconds, accepted from a single frame.
+///
+/// When the window is hidden or the event loop stalls, the next frame can
+/// report an interval of several seconds. Clamping it keeps the animation from
+/// jumping ahead.
+const MAX_FRAME_INTERVAL: f64 = 0.25;
+
+/// How the clock turns real frame intervals into animation time.
+#[derive(Clone, Copy, Debug, Data, PartialEq)]
+pub enum ClockMode {
+    /// Advance by exactly the (scaled) real time elapsed between frames.
+    RealTime,
+    /// Advance only in whole steps of the given length in seconds.
+    ///
+    /// Real time is accumulated and released one step at a time, so the
+    /// animation time is always a multiple of the step and playback is
+    /// reproducible regardless of frame timing.
+    FixedStep(f64),
+}
+
+/// Converts animation frame intervals into animation time.
+#[derive(Clone, Debug, Data)]
+pub struct AnimationClock {
+    paused: bool,
+    time_scale: f64,
+    mode: ClockMode,
+    /// Scaled real time not yet released in fixed-step mode.
+    accumulator: f64,
+}
+
+impl AnimationClock {
+    /// Creates a running real-time clock with a time scale of 1.0.
+    pub fn new() -> Self {
+        AnimationClock {
+            paused: false,
+            time_scale: 1.0,
+            mode: ClockMode::RealTime,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Advances the clock by one animation frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - The real time elapsed since the previous frame in
+    ///   nanoseconds, as carried by `Event::AnimFrame`.
+    ///
+    /// # Returns
+    ///
+    /// The amount of animation time, in seconds, to add for this frame.
+    pub fn tick(&mut self, interval: u64) -> f64 {
+        if self.paused {
+            return 0.0;
+        }
+
+        let real = (interval as f64 * 1e-9).min(MAX_FRAME_INTERVAL);
+        let scaled = real * self.time_scale;
+
+        match self.mode {
+            ClockMode::RealTime => scaled,
+            ClockMode::FixedStep(step) => {
+                self.accumulator += scaled;
+                let steps = (self.accumulator / step).floor();
+                self.accumulator -= steps * step;
+                steps * step
+            }
+        }
+    }
+
+    /// Pauses or resumes the clock. A paused clock advances by nothing.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Returns `true` if the clock is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns the factor applied to real time.
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Sets the factor applied to real time.
+    ///
+    /// A scale of 2.0 plays the animation twice as fast, 0.5 at half speed.
+    /// Negative values are clamped to zero.
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    /// Returns the current clock mode.
+    pub fn mode(&self) -> ClockMode {
+        self.mode
+    }
+
+    /// Switches the clock mode, discarding any partially accumulated step.
+    ///
+    /// Fixed steps that are not strictly positive fall back to
+    /// [`DEFAULT_FIXED_STEP`].
+    pub fn set_mode(&mut self, mode: ClockMode) {
+        self.mode = match mode {
+            ClockMode::FixedStep(step) if step.is_nan() || step <= 0.0 => {
+                ClockMode::FixedStep(DEFAULT_FIXED_STEP)
+            }
+            mode => mode,
+        };
+        self.accumulator = 0.0;
+    }
+}
+
+impl Default for AnimationClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lets the keyboard control the animation clock of the heart it wraps.
+///
+/// Space pauses and resumes, the up and down arrows change the playback
+/// speed and `f` toggles fixed-step mode. With a timeline, the left and right
+/// arrows skip through it and Home restarts it.
+pub struct ClockKeys;
+
+impl<W: Widget<AppState>> Controller<AppState, W> for ClockKeys {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        match event {
+            // Take keyboard focus so the clock controls work right away
+            Event::WindowConnected => ctx.request_focus(),
+            Event::KeyDown(key) => match &key.key {
+                KbKey::Character(c) if c == " " => {
+                    let paused = data.clock.is_paused();
+                    // Resuming at the end of a timeline replays it
+                    if let Some(timeline) = &data.timeline {
+                        if paused && data.time >= timeline.duration() {
+                            data.seek(0.0);
+                        }
+                    }
+                    data.clock.set_paused(!paused);
+                }
+                KbKey::Character(c) if c.eq_ignore_ascii_case("f") => {
+                    let mode = match data.clock.mode() {
+                        ClockMode::RealTime => ClockMode::FixedStep(DEFAULT_FIXED_STEP),
+                        ClockMode::FixedStep(_) => ClockMode::RealTime,
+                    };
+                    data.clock.set_mode(mode);
+                }
+                KbKey::ArrowUp => {
+                    let scale = data.clock.time_scale() * TIME_SCALE_STEP;
+                    data.clock.set_time_scale(scale);
+                }
+                KbKey::ArrowDown => {
+                    let scale = data.clock.time_scale() / TIME_SCALE_STEP;
+                    data.clock.set_time_scale(scale);
+                }
+                KbKey::ArrowLeft | KbKey::ArrowRight | KbKey::Home => {
+                    if let Some(timeline) = &data.timeline {
+                        let time = match &key.key {
+                            KbKey::ArrowLeft => data.time - SEEK_STEP,
+                            KbKey::ArrowRight => data.time + SEEK_STEP,
+                            _ => 0.0,
+                        };
+                        let time = time.clamp(0.0, timeline.duration());
+                        data.seek(time);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        child.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &AppState,
+        env: &Env,
+    ) {
+        if let LifeCycle::BuildFocusChain = event {
+            ctx.register_for_focus();
+        }
+        child.lifecycle(ctx, event, data, env);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Nanoseconds in a millisecond.
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn real_time_follows_frame_intervals() {
+        let mut clock = AnimationClock::new();
+        assert!((clock.tick(16 * MS) - 0.016).abs() < 1e-12);
+        assert!((clock.tick(7 * MS) - 0.007).abs() < 1e-12);
+    }
+
+    #[test]
+    fn long_intervals_are_clamped() {
+        let mut clock = AnimationClock::new();
+        assert_eq!(clock.tick(3_000 * MS), MAX_FRAME_INTERVAL);
+    }
+
+    #[test]
+    fn paused_clock_stands_still() {
+        let mut clock = AnimationClock::new();
+        clock.set_paused(true);
+        assert!(clock.is_paused());
+        assert_eq!(clock.tick(16 * MS), 0.0);
+        clock.set_paused(false);
+        assert!(clock.tick(16 * MS) > 0.0);
+    }
+
+    #[test]
+    fn time_scale_applies_and_never_goes_negative() {
+        let mut clock = AnimationClock::new();
+        clock.set_time_scale(2.0);
+        assert!((clock.tick(100 * MS) - 0.2).abs() < 1e-12);
+        clock.set_time_scale(-1.0);
+        assert_eq!(clock.time_scale(), 0.0);
+        assert_eq!(clock.tick(100 * MS), 0.0);
+    }
+
+    #[test]
+    fn fixed_step_releases_whole_steps() {
+        let mut clock = AnimationClock::new();
+        clock.set_mode(ClockMode::FixedStep(0.25));
+        assert_eq!(clock.tick(100 * MS), 0.0);
+        assert_eq!(clock.tick(100 * MS), 0.0);
+        assert_eq!(clock.tick(100 * MS), 0.25);
+        // The 0.05 s left over carries into the next frame
+        assert_eq!(clock.tick(250 * MS), 0.25);
+    }
+
+    #[test]
+    fn switching_modes_discards_the_partial_step() {
+        let mut clock = AnimationClock::new();
+        clock.set_mode(ClockMode::FixedStep(0.25));
+        clock.tick(200 * MS);
+        clock.set_mode(ClockMode::FixedStep(0.25));
+        assert_eq!(clock.tick(100 * MS), 0.0);
+    }
+
+    #[test]
+    fn invalid_fixed_steps_fall_back_to_the_default() {
+        let mut clock = AnimationClock::new();
+        for step in [0.0, -1.0, f64::NAN] {
+            clock.set_mode(ClockMode::FixedStep(step));
+            assert_eq!(clock.mode(), ClockMode::FixedStep(DEFAULT_FIXED_STEP));
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Color animation in step with the beat.
+//!
+//! A [`ColorRamp`] lists the colors the heart's fill passes through as it
+//! contracts, driven by the same envelope as its size, so the heart flushes
+//! on every beat. Colors are blended in the Oklab color space, where equal
+//! steps look equally large and the blends stay saturated instead of
+//! turning muddy.
+
+use std::str::FromStr;
+
+use druid::Color;
+
+/// How far a [`ColorRamp::Flush`] darkens and lightens the fill unless
+/// configured otherwise.
+pub const DEFAULT_FLUSH_DEPTH: f64 = 0.3;
+
+/// The colors the heart's fill passes through over a beat.
+#[derive(Clone, Debug)]
+pub enum ColorRamp {
+    /// The fill color itself, darkened by the given fraction of its
+    /// lightness at rest and lightened by the same fraction of the
+    /// remaining lightness at the peak of a beat.
+    Flush(f64),
+    /// Explicit colors, spread evenly from rest to the peak of a beat.
+    Stops(Vec<Color>),
+}
+
+impl ColorRamp {
+    /// Returns the color of the fill at a contraction of `pulse`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fill` - The heart's configured fill color, which a flush is
+    ///   based on.
+    /// * `pulse` - How far the heart is contracted, from 0.0 at rest to 1.0
+    ///   at the peak of a beat.
+    pub fn color(&self, fill: Color, pulse: f64) -> Color {
+        let pulse = pulse.clamp(0.0, 1.0);
+        match self {
+            ColorRamp::Flush(depth) => {
+                let depth = depth.clamp(0.0, 1.0);
+                let (lightness, a, b, alpha) = to_oklab(fill);
+                let rest = lightness * (1.0 - depth);
+                let peak = lightness + (1.0 - lightness) * depth;
+                from_oklab(rest + (peak - rest) * pulse, a, b, alpha)
+            }
+            ColorRamp::Stops(stops) => match stops.as_slice() {
+                [] => fill,
+                [only] => *only,
+                stops => {
+                    let position = pulse * (stops.len() - 1) as f64;
+                    let index = (position as usize).min(stops.len() - 2);
+                    mix(stops[index], stops[index + 1], position - index as f64)
+                }
+            },
+        }
+    }
+}
+
+/// Parses `flush`, `flush:DEPTH` such as `flush:0.4`, or at least two
+/// comma-separated colors such as `#800000,#ff4060`.
+impl FromStr for ColorRamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("flush") {
+            return Ok(ColorRamp::Flush(DEFAULT_FLUSH_DEPTH));
+        }
+        if let Some((prefix, depth)) = s.split_once(':') {
+            if prefix.trim().eq_ignore_ascii_case("flush") {
+                return match depth.trim().parse::<f64>() {
+                    Ok(depth) if (0.0..=1.0).contains(&depth) => Ok(ColorRamp::Flush(depth)),
+                    _ => Err(format!("`{}` is not a flush depth between 0 and 1", depth)),
+                };
+            }
+        }
+
+        let stops = s
+            .split(',')
+            .map(|color| parse_color(color.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if stops.len() < 2 {
+            return Err(format!(
+                "`{}` is not a color ramp; expected `flush`, `flush:DEPTH` or at least two \
+                 comma-separated colors",
+                s
+            ));
+        }
+        Ok(ColorRamp::Stops(stops))
+    }
+}
+
+/// Parses a color given as a hex string such as `#ff0000`.
+pub fn parse_color(s: &str) -> Result<Color, String> {
+    Color::from_hex_str(s).map_err(|err| format!("`{}` is not a color: {}", s, err))
+}
+
+/// Returns a color a fraction `t` of the way from `a` to `b`, blended in
+/// Oklab.
+pub fn mix(a: Color, b: Color, t: f64) -> Color {
+    let (l0, a0, b0, alpha0) = to_oklab(a);
+    let (l1, a1, b1, alpha1) = to_oklab(b);
+    from_oklab(
+        l0 + (l1 - l0) * t,
+        a0 + (a1 - a0) * t,
+        b0 + (b1 - b0) * t,
+        alpha0 + (alpha1 - alpha0) * t,
+    )
+}
+
+/// Converts a color to Oklab lightness, green-red and blue-yellow axes,
+/// keeping its opacity.
+fn to_oklab(color: Color) -> (f64, f64, f64, f64) {
+    let (r, g, b, alpha) = color.as_rgba();
+    let (r, g, b) = (to_linear(r), to_linear(g), to_linear(b));
+
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    (
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+        alpha,
+    )
+}
+
+/// Converts Oklab coordinates back to a color, clipping it to sRGB.
+fn from_oklab(lightness: f64, a: f64, b: f64, alpha: f64) -> Color {
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+
+    Color::rgba(
+        to_srgb(4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s),
+        to_srgb(-1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s),
+        to_srgb(-0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s),
+        alpha.clamp(0.0, 1.0),
+    )
+}
+
+/// Converts an sRGB channel to linear light.
+fn to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear-light channel to sRGB, clipped to its range.
+fn to_srgb(c: f64) -> f64 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that two colors differ by at most one step of 8-bit sRGB.
+    fn assert_close(a: Color, b: Color) {
+        let (a, b) = (a.as_rgba(), b.as_rgba());
+        let channels = [(a.0, b.0), (a.1, b.1), (a.2, b.2), (a.3, b.3)];
+        assert!(
+            channels.iter().all(|(x, y)| (x - y).abs() <= 1.0 / 255.0),
+            "{:?} is not {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn colors_parse_from_hex() {
+        assert_eq!(parse_color("#ff8000"), Ok(Color::rgb8(255, 128, 0)));
+        assert_eq!(parse_color("#ff800080"), Ok(Color::rgba8(255, 128, 0, 128)));
+        assert!(parse_color("#12345").is_err());
+        assert!(parse_color("red").is_err());
+    }
+
+    #[test]
+    fn oklab_round_trips() {
+        for color in [
+            Color::BLACK,
+            Color::WHITE,
+            Color::rgb8(255, 0, 0),
+            Color::rgba8(30, 128, 255, 64),
+        ] {
+            let (lightness, a, b, alpha) = to_oklab(color);
+            assert_close(from_oklab(lightness, a, b, alpha), color);
+        }
+    }
+
+    #[test]
+    fn mixes_run_between_their_ends() {
+        let (red, blue) = (Color::rgb8(255, 0, 0), Color::rgb8(0, 0, 255));
+        assert_close(mix(red, blue, 0.0), red);
+        assert_close(mix(red, blue, 1.0), blue);
+
+        // Halfway in Oklab lightness is much lighter than halfway in sRGB
+        let (r, g, b, _) = mix(Color::BLACK, Color::WHITE, 0.5).as_rgba();
+        assert!((r - 0.3886).abs() < 1e-3, "{}", r);
+        assert!((r - g).abs() < 1e-6 && (g - b).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ramps_parse() {
+        let depth = |s: &str| match s.parse::<ColorRamp>() {
+            Ok(ColorRamp::Flush(depth)) => Some(depth),
+            _ => None,
+        };
+        assert_eq!(depth("flush"), Some(DEFAULT_FLUSH_DEPTH));
+        assert_eq!(depth(" FLUSH "), Some(DEFAULT_FLUSH_DEPTH));
+        assert_eq!(depth("flush:0.4"), Some(0.4));
+        assert_eq!(depth("FLUSH: 0.4"), Some(0.4));
+
+        match "#800000, #ff4060".parse::<ColorRamp>() {
+            Ok(ColorRamp::Stops(stops)) => assert_eq!(
+                stops,
+                [Color::rgb8(0x80, 0, 0), Color::rgb8(0xff, 0x40, 0x60)]
+            ),
+            other => panic!("{:?} is not two stops", other),
+        }
+
+        for s in ["flush:", "flush:1.5", "#800000", "#800000,pink", ""] {
+            assert!(s.parse::<ColorRamp>().is_err(), "{:?} should not parse", s);
+        }
+    }
+
+    #[test]
+    fn ramps_follow_the_pulse() {
+        let fill = Color::rgb8(200, 40, 60);
+        let stops = ColorRamp::Stops(vec![Color::BLACK, fill, Color::WHITE]);
+        assert_close(stops.color(fill, 0.0), Color::BLACK);
+        assert_close(stops.color(fill, 0.5), fill);
+        assert_close(stops.color(fill, 1.0), Color::WHITE);
+        assert_close(stops.color(fill, 7.0), Color::WHITE);
+
+        assert_close(ColorRamp::Flush(0.0).color(fill, 0.3), fill);
+        let flush = ColorRamp::Flush(DEFAULT_FLUSH_DEPTH);
+        let lightness = |pulse| to_oklab(flush.color(fill, pulse)).0;
+        let fill_lightness = to_oklab(fill).0;
+        assert!(lightness(0.0) < fill_lightness && fill_lightness < lightness(1.0));
+    }
+}
@@ -0,0 +1,803 @@
+//! Configuration files describing the window and the heart.
+//!
+//! A configuration file is written in TOML (`.toml`) or JSON (`.json`) and
+//! may set any subset of the options:
+//!
+//! ```toml
+//! [window]
+//! width = 400
+//! height = 400
+//! title = "Beating Heart"
+//!
+//! [heart]
+//! bpm = 72
+//! amplitude = 0.1
+//! envelope = "lub-dub"
+//! theme = "light"
+//! fill = "#ff0000"
+//! color_ramp = "flush:0.3"
+//! stroke = "#000000"
+//! stroke_width = 4
+//! stroke_align = "centered"
+//! line_join = "round"
+//! line_cap = "butt"
+//! dash = [8, 4]
+//! paint_order = "fill-first"
+//! background = "#ffffff"
+//! gradient = "radial"
+//! shadow = true
+//! glow = true
+//! gloss = false
+//!
+//! [heart.shape]
+//! name = "heart"
+//! lobe_roundness = 1.0
+//! tip_sharpness = 0.25
+//! cleft_depth = 0.25
+//! ```
+//!
+//! The shape's `name` picks an icon from the built-in
+//! [`ShapeLibrary`](crate::shapes::ShapeLibrary); a `path` of SVG path data
+//! pulses with a custom icon instead. The other parameters apply to the
+//! `heart` and `outline` icons.
+//!
+//! Listing `[[hearts]]` shows a dashboard of several hearts instead, drawn
+//! like `[heart]` but each with its own label, rate and color. A heart with
+//! a `frame` of `[x, y, width, height]`, as fractions of the window, is
+//! placed there; the others fill a grid of `window.columns` columns:
+//!
+//! ```toml
+//! [[hearts]]
+//! label = "Alice"
+//! bpm = 62
+//! fill = "#3080ff"
+//!
+//! [[hearts]]
+//! label = "Bob"
+//! bpm = 95
+//! frame = [0.5, 0.0, 0.5, 1.0]
+//! ```
+//!
+//! [`watch`] polls a file for changes, so a running application can pick up
+//! edits without restarting; [`watch_app`] pushes each reloaded heart into
+//! the application right away.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use druid::{
+    kurbo::Rect,
+    piet::{LineCap, LineJoin},
+    Color, ExtEventSink, Target,
+};
+use serde::{Deserialize, Deserializer};
+
+use crate::beat;
+use crate::cli::{parse_line_cap, parse_line_join, HeartArgs};
+use crate::color::{parse_color, ColorRamp};
+use crate::dashboard::{HeartEntry, Hearts};
+use crate::envelope::EnvelopeProfile;
+use crate::geometry::HeartShape;
+use crate::paint::{PaintOrder, StrokeAlign};
+use crate::shapes::Icon;
+use crate::state::AppState;
+use crate::style::Gradient;
+use crate::theme::Theme;
+use crate::timeline::Timeline;
+use crate::widget::SET_PAINTER;
+
+/// How often [`watch`] checks the file for changes unless told otherwise.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// An error raised while loading a configuration file.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Reading the file failed.
+    Io(io::Error),
+    /// The file's extension is neither `.toml` nor `.json`.
+    UnknownFormat(PathBuf),
+    /// The file is not valid TOML or JSON, or has unexpected contents.
+    Parse(String),
+    /// A value is outside the accepted range.
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "reading failed: {}", err),
+            ConfigError::UnknownFormat(path) => write!(
+                f,
+                "`{}` is neither a .toml nor a .json file",
+                path.display()
+            ),
+            ConfigError::Parse(err) => f.write_str(err),
+            ConfigError::Invalid(err) => f.write_str(err),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+/// The contents of a configuration file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Settings of the application window.
+    pub window: WindowConfig,
+    /// Settings of the heart.
+    pub heart: HeartConfig,
+    /// The hearts of a dashboard; empty for a single heart.
+    pub hearts: Vec<HeartEntryConfig>,
+}
+
+/// Settings of the application window. These only apply at startup.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    /// Initial width of the window.
+    pub width: Option<f64>,
+    /// Initial height of the window.
+    pub height: Option<f64>,
+    /// Window title.
+    pub title: Option<String>,
+    /// Number of grid columns of a dashboard.
+    pub columns: Option<usize>,
+}
+
+/// Settings of the heart.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeartConfig {
+    /// Heart rate in beats per minute.
+    pub bpm: Option<f64>,
+    /// Relative size change at the peak of a beat.
+    pub amplitude: Option<f64>,
+    /// Envelope shaping each beat.
+    #[serde(deserialize_with = "from_str")]
+    pub envelope: Option<EnvelopeProfile>,
+    /// Color theme, whose colors apply wherever none are set.
+    #[serde(deserialize_with = "from_str")]
+    pub theme: Option<Theme>,
+    /// Fill color.
+    #[serde(deserialize_with = "color")]
+    pub fill: Option<Color>,
+    /// Colors the fill passes through over each beat.
+    #[serde(deserialize_with = "from_str")]
+    pub color_ramp: Option<ColorRamp>,
+    /// Outline color.
+    #[serde(deserialize_with = "color")]
+    pub stroke: Option<Color>,
+    /// Outline width in pixels.
+    pub stroke_width: Option<f64>,
+    /// Where the outline lies relative to the heart's edge.
+    #[serde(deserialize_with = "from_str")]
+    pub stroke_align: Option<StrokeAlign>,
+    /// How the outline turns corners.
+    #[serde(deserialize_with = "line_join")]
+    pub line_join: Option<LineJoin>,
+    /// How the ends of dashes are drawn.
+    #[serde(deserialize_with = "line_cap")]
+    pub line_cap: Option<LineCap>,
+    /// Dash pattern of the outline; empty for a solid outline.
+    pub dash: Option<Vec<f64>>,
+    /// Order in which the fill and the outline are painted.
+    #[serde(deserialize_with = "from_str")]
+    pub paint_order: Option<PaintOrder>,
+    /// Background color.
+    #[serde(deserialize_with = "color")]
+    pub background: Option<Color>,
+    /// Fill shading.
+    #[serde(deserialize_with = "from_str")]
+    pub gradient: Option<Gradient>,
+    /// Whether a shadow falls beneath the heart.
+    pub shadow: Option<bool>,
+    /// Whether a glow surrounds the heart.
+    pub glow: Option<bool>,
+    /// Whether a glossy highlight lies on the heart.
+    pub gloss: Option<bool>,
+    /// Parameters of the heart's outline.
+    pub shape: ShapeConfig,
+}
+
+/// The shape to pulse with and the parameters of the heart's outline; see
+/// [`Icon`] and [`HeartShape`].
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShapeConfig {
+    /// Name of an icon from the built-in library.
+    pub name: Option<String>,
+    /// SVG path data of a custom icon, which takes precedence over the name.
+    pub path: Option<String>,
+    /// How far the lobes bulge upwards.
+    pub lobe_roundness: Option<f64>,
+    /// How pointed the bottom tip is.
+    pub tip_sharpness: Option<f64>,
+    /// How far the cleft between the lobes reaches down.
+    pub cleft_depth: Option<f64>,
+}
+
+/// Settings of one heart of a dashboard. Unset values are taken from the
+/// single heart's settings.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeartEntryConfig {
+    /// The name shown below the heart.
+    pub label: Option<String>,
+    /// Heart rate in beats per minute.
+    pub bpm: Option<f64>,
+    /// Relative size change at the peak of a beat.
+    pub amplitude: Option<f64>,
+    /// Fill color.
+    #[serde(deserialize_with = "color")]
+    pub fill: Option<Color>,
+    /// Phase within the beat the heart starts at, from 0.0 to 1.0.
+    pub phase: Option<f64>,
+    /// Position and size as `[x, y, width, height]`, in fractions of the
+    /// window.
+    pub frame: Option<[f64; 4]>,
+}
+
+impl Config {
+    /// Loads and validates a configuration file.
+    ///
+    /// The format is chosen by the file's extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let text = fs::read_to_string(path)?;
+
+        let config: Config = match extension.as_deref() {
+            Some("toml") => {
+                toml::from_str(&text).map_err(|err| ConfigError::Parse(err.to_string()))?
+            }
+            Some("json") => {
+                serde_json::from_str(&text).map_err(|err| ConfigError::Parse(err.to_string()))?
+            }
+            _ => return Err(ConfigError::UnknownFormat(path.to_path_buf())),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Returns the heart options with the file's values filled in.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The heart options from the command line.
+    /// * `keep` - Returns `true` for the names of options that take
+    ///   precedence over the file, such as those given explicitly on the
+    ///   command line.
+    pub fn heart_args(&self, args: &HeartArgs, keep: impl Fn(&str) -> bool) -> HeartArgs {
+        let mut args = args.clone();
+        self.heart.apply(&mut args, keep);
+        args
+    }
+
+    /// Returns the hearts of the dashboard the file lists, drawn with the
+    /// options `args` where they set nothing else.
+    pub fn dashboard_hearts(&self, args: &HeartArgs) -> Vec<HeartEntry> {
+        self.hearts
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| entry.entry(index, args))
+            .collect()
+    }
+
+    /// Checks that every value lies in its accepted range.
+    fn validate(&self) -> Result<(), ConfigError> {
+        let window = &self.window;
+        check("window.width", window.width, f64::MIN_POSITIVE, f64::MAX)?;
+        check("window.height", window.height, f64::MIN_POSITIVE, f64::MAX)?;
+
+        let heart = &self.heart;
+        check("heart.bpm", heart.bpm, beat::MIN_BPM, beat::MAX_BPM)?;
+        check("heart.amplitude", heart.amplitude, 0.0, beat::MAX_AMPLITUDE)?;
+        check("heart.stroke_width", heart.stroke_width, 0.0, f64::MAX)?;
+        for &length in heart.dash.iter().flatten() {
+            check("heart.dash", Some(length), 0.0, f64::MAX)?;
+        }
+        // An empty pattern leaves the outline solid
+        let dash = heart.dash.as_deref().unwrap_or_default();
+        if !dash.is_empty() && dash.iter().sum::<f64>() == 0.0 {
+            return Err(ConfigError::Invalid(
+                "`heart.dash` must have a dash or gap longer than zero".to_string(),
+            ));
+        }
+
+        let shape = &heart.shape;
+        check("heart.shape.lobe_roundness", shape.lobe_roundness, 0.0, 2.0)?;
+        check("heart.shape.tip_sharpness", shape.tip_sharpness, 0.0, 1.0)?;
+        check("heart.shape.cleft_depth", shape.cleft_depth, 0.0, 1.0)?;
+        let icon = match (&shape.path, &shape.name) {
+            (Some(path), _) => Icon::from_svg(path).map(|_| ()),
+            (None, Some(name)) => name.parse::<Icon>().map(|_| ()),
+            (None, None) => Ok(()),
+        };
+        icon.map_err(|err| ConfigError::Invalid(format!("`heart.shape`: {}", err)))?;
+
+        if window.columns == Some(0) {
+            return Err(ConfigError::Invalid(
+                "`window.columns` must be at least 1".to_string(),
+            ));
+        }
+        for (index, entry) in self.hearts.iter().enumerate() {
+            entry.validate(index)?;
+        }
+        Ok(())
+    }
+}
+
+impl HeartConfig {
+    /// Overwrites options with the values set in the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The options to update.
+    /// * `keep` - Returns `true` for the names of options that must not be
+    ///   overwritten, such as those given explicitly on the command line.
+    pub fn apply(&self, args: &mut HeartArgs, keep: impl Fn(&str) -> bool) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>, keep: bool) {
+            if let (Some(value), false) = (value, keep) {
+                *target = value.clone();
+            }
+        }
+        fn set_some<T: Clone>(target: &mut Option<T>, value: &Option<T>, keep: bool) {
+            if value.is_some() && !keep {
+                *target = value.clone();
+            }
+        }
+
+        set(&mut args.bpm, &self.bpm, keep("bpm"));
+        set(&mut args.amplitude, &self.amplitude, keep("amplitude"));
+        set(&mut args.envelope, &self.envelope, keep("envelope"));
+        set_some(&mut args.theme, &self.theme, keep("theme"));
+        set_some(&mut args.fill, &self.fill, keep("fill"));
+        set_some(&mut args.color_ramp, &self.color_ramp, keep("color_ramp"));
+        set_some(&mut args.stroke, &self.stroke, keep("stroke"));
+        set_some(
+            &mut args.stroke_width,
+            &self.stroke_width,
+            keep("stroke_width"),
+        );
+        set(
+            &mut args.stroke_align,
+            &self.stroke_align,
+            keep("stroke_align"),
+        );
+        set(&mut args.line_join, &self.line_join, keep("line_join"));
+        set(&mut args.line_cap, &self.line_cap, keep("line_cap"));
+        set_some(&mut args.dash, &self.dash, keep("dash"));
+        set(
+            &mut args.paint_order,
+            &self.paint_order,
+            keep("paint_order"),
+        );
+        set_some(&mut args.background, &self.background, keep("background"));
+        set(&mut args.gradient, &self.gradient, keep("gradient"));
+        set(&mut args.shadow, &self.shadow, keep("shadow"));
+        set(&mut args.glow, &self.glow, keep("glow"));
+        set(&mut args.gloss, &self.gloss, keep("gloss"));
+
+        let shape = &self.shape;
+        let configured = shape.name.is_some()
+            || shape.path.is_some()
+            || shape.lobe_roundness.is_some()
+            || shape.tip_sharpness.is_some()
+            || shape.cleft_depth.is_some();
+        if configured && !keep("shape") && !keep("shape_path") {
+            args.shape = Some(self.shape());
+        }
+    }
+
+    /// Returns the shape to pulse with, with defaults for unset parameters.
+    ///
+    /// A name or path that fails to load, which [`Config::load`] rejects,
+    /// yields the classic heart.
+    pub fn shape(&self) -> Icon {
+        let default = HeartShape::default();
+        let shape = &self.shape;
+        let heart = HeartShape {
+            lobe_roundness: shape.lobe_roundness.unwrap_or(default.lobe_roundness),
+            tip_sharpness: shape.tip_sharpness.unwrap_or(default.tip_sharpness),
+            cleft_depth: shape.cleft_depth.unwrap_or(default.cleft_depth),
+        };
+        let icon = match (&shape.path, &shape.name) {
+            (Some(path), _) => Icon::from_svg(path).ok(),
+            (None, Some(name)) => name.parse().ok(),
+            (None, None) => None,
+        };
+        match icon {
+            Some(Icon::Outline(_)) => Icon::Outline(heart),
+            Some(Icon::Heart(_)) | None => Icon::Heart(heart),
+            Some(icon) => icon,
+        }
+    }
+}
+
+impl HeartEntryConfig {
+    /// Checks that every value lies in its accepted range and that the
+    /// frame fits in the window. Errors name the heart by its position
+    /// `index` in the list and by its label, if set.
+    fn validate(&self, index: usize) -> Result<(), ConfigError> {
+        self.check_values(index)
+            .map_err(|err| match (err, &self.label) {
+                (ConfigError::Invalid(message), Some(label)) => {
+                    ConfigError::Invalid(format!("{} (heart `{}`)", message, label))
+                }
+                (err, _) => err,
+            })
+    }
+
+    /// Does the checks of [`HeartEntryConfig::validate`], naming only the
+    /// heart's position.
+    fn check_values(&self, index: usize) -> Result<(), ConfigError> {
+        let name = |field: &str| format!("hearts[{}].{}", index, field);
+        check(&name("bpm"), self.bpm, beat::MIN_BPM, beat::MAX_BPM)?;
+        check(&name("amplitude"), self.amplitude, 0.0, beat::MAX_AMPLITUDE)?;
+        // Any phase wraps into the beat, so it only has to be a number
+        check(&name("phase"), self.phase, f64::MIN, f64::MAX)?;
+        if let Some([x, y, width, height]) = self.frame {
+            for value in [x, y] {
+                check(&name("frame"), Some(value), 0.0, 1.0)?;
+            }
+            for value in [width, height] {
+                check(&name("frame"), Some(value), f64::MIN_POSITIVE, 1.0)?;
+            }
+            // Leave room for fractions such as thirds that do not add up
+            // to exactly 1
+            if x + width > 1.0 + 1e-9 || y + height > 1.0 + 1e-9 {
+                return Err(ConfigError::Invalid(format!(
+                    "`{}` does not fit in the window",
+                    name("frame")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the dashboard entry described by these settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The position of the heart in the list, used to name it
+    ///   when no label is set.
+    /// * `args` - The single heart's options, used for unset values.
+    pub fn entry(&self, index: usize, args: &HeartArgs) -> HeartEntry {
+        let label = match &self.label {
+            Some(label) => label.clone(),
+            None => format!("Heart {}", index + 1),
+        };
+        let entry = HeartEntry::new(label, self.bpm.unwrap_or(args.bpm))
+            .amplitude(self.amplitude.unwrap_or(args.amplitude))
+            .fill(self.fill.unwrap_or_else(|| args.painter().fill))
+            .phase(self.phase.unwrap_or(0.0));
+        match self.frame {
+            Some([x, y, width, height]) => {
+                entry.frame(Rect::from_origin_size((x, y), (width, height)))
+            }
+            None => entry,
+        }
+    }
+}
+
+/// Watches a configuration file and reloads it whenever it changes.
+///
+/// The file is polled on a background thread. `on_change` receives the
+/// result of every reload, including errors, and returns `false` to stop
+/// watching.
+///
+/// # Arguments
+///
+/// * `path` - The file to watch.
+/// * `interval` - How often to check the file's modification time.
+/// * `on_change` - Called with the reloaded configuration.
+pub fn watch<F>(
+    path: impl Into<PathBuf>,
+    interval: Duration,
+    mut on_change: F,
+) -> thread::JoinHandle<()>
+where
+    F: FnMut(Result<Config, ConfigError>) -> bool + Send + 'static,
+{
+    let path = path.into();
+    thread::spawn(move || {
+        let mut last_modified = modified(&path);
+        loop {
+            thread::sleep(interval);
+            let current = modified(&path);
+            // Skip reloads while the file is missing, e.g. mid-save
+            if current != last_modified && current.is_some() {
+                last_modified = current;
+                if !on_change(Config::load(&path)) {
+                    break;
+                }
+            }
+        }
+    })
+}
+
+/// Watches a configuration file and pushes the heart it describes into a
+/// running application whenever it changes.
+///
+/// Every reload is sent as a [`SET_PAINTER`] command; errors are reported on
+/// standard error and leave the heart as it was. Watching stops once the
+/// application has quit.
+///
+/// # Arguments
+///
+/// * `path` - The file to watch.
+/// * `args` - The heart options from the command line.
+/// * `keep` - Returns `true` for the names of options that take precedence
+///   over the file, as for [`Config::heart_args`].
+/// * `sink` - Reaches the running application.
+/// * `on_reload` - Pushes the rest of the reloaded configuration, along with
+///   the heart options it results in, into the application, e.g.
+///   [`reload_heart`] or [`reload_hearts`].
+pub fn watch_app<K, F>(
+    path: impl Into<PathBuf>,
+    args: HeartArgs,
+    keep: K,
+    sink: ExtEventSink,
+    mut on_reload: F,
+) -> thread::JoinHandle<()>
+where
+    K: Fn(&str) -> bool + Send + 'static,
+    F: FnMut(&Config, &HeartArgs, &ExtEventSink) + Send + 'static,
+{
+    let path = path.into();
+    let name = path.display().to_string();
+    watch(path, DEFAULT_POLL_INTERVAL, move |config| {
+        let config = match config {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("error: failed to reload `{}`: {}", name, err);
+                return true;
+            }
+        };
+
+        let args = config.heart_args(&args, &keep);
+        on_reload(&config, &args, &sink);
+        // Stop watching once the application has quit
+        let builder = args.builder();
+        let look = (builder.painter(), builder.themed());
+        sink.submit_command(SET_PAINTER, look, Target::Auto).is_ok()
+    })
+}
+
+/// Returns a handler for [`watch_app`] applying the beat of a reloaded
+/// configuration to an application showing a single heart, whose data is
+/// an [`AppState`], starting from the heart options `args` it was loaded
+/// with.
+///
+/// The heart rate and amplitude are only set when a reload changes them, so
+/// saving an unrelated option keeps the rate set by the slider, a live
+/// source or OSC.
+pub fn reload_heart(
+    args: &HeartArgs,
+) -> impl FnMut(&Config, &HeartArgs, &ExtEventSink) + Send + 'static {
+    let beat_of = |args: &HeartArgs| {
+        let state = args.builder().state();
+        (state.bpm, state.amplitude)
+    };
+    let mut last = beat_of(args);
+    move |_, args, sink| {
+        let (bpm, amplitude) = beat_of(args);
+        let changed = (bpm != last.0, amplitude != last.1);
+        last = (bpm, amplitude);
+        let envelope = args.envelope;
+        sink.add_idle_callback(move |data: &mut AppState| {
+            if changed.0 {
+                data.bpm = bpm;
+            }
+            if changed.1 {
+                data.amplitude = amplitude;
+            }
+            // Keep the recorded beats on the new envelope's contraction
+            if let Some(timeline) = &data.timeline {
+                let mut timeline = Timeline::clone(timeline);
+                timeline.align_to(&envelope);
+                data.set_timeline(Some(Arc::new(timeline)));
+            }
+        });
+    }
+}
+
+/// Applies the hearts of a reloaded configuration to an application showing
+/// a dashboard, whose data is [`Hearts`]; a handler for [`watch_app`].
+///
+/// Hearts are matched to the ones shown by their label, so hearts still
+/// listed keep their beat position wherever they moved, and new hearts
+/// start fresh. Hearts sharing a label are matched in order.
+pub fn reload_hearts(config: &Config, args: &HeartArgs, sink: &ExtEventSink) {
+    let mut hearts = config.dashboard_hearts(args);
+    sink.add_idle_callback(move |data: &mut Hearts| {
+        let mut shown: Vec<Option<&HeartEntry>> = data.iter().map(Some).collect();
+        for entry in hearts.iter_mut() {
+            let old = shown
+                .iter_mut()
+                .find(|old| old.is_some_and(|old| old.label == entry.label))
+                .and_then(Option::take);
+            if let Some(old) = old {
+                let (bpm, amplitude) = (entry.state.bpm, entry.state.amplitude);
+                entry.state = old.state.clone();
+                entry.state.bpm = bpm;
+                entry.state.amplitude = amplitude;
+            }
+        }
+        *data = Arc::new(hearts);
+    });
+}
+
+/// Returns the time a file was last modified, if it can be read.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Checks that an optional value lies between `min` and `max`, inclusive.
+fn check(name: &str, value: Option<f64>, min: f64, max: f64) -> Result<(), ConfigError> {
+    match value {
+        Some(value) if !(min..=max).contains(&value) => Err(ConfigError::Invalid(format!(
+            "`{}` is out of range: {}",
+            name, value
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Deserializes an optional value from a string through its `FromStr`.
+fn from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let text = String::deserialize(deserializer)?;
+    text.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+/// Deserializes an optional color from a hex string.
+fn color<'de, D>(deserializer: D) -> Result<Option<Color>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    parse_color(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Deserializes an optional line join from its name.
+fn line_join<'de, D>(deserializer: D) -> Result<Option<LineJoin>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    parse_line_join(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Deserializes an optional line cap from its name.
+fn line_cap<'de, D>(deserializer: D) -> Result<Option<LineCap>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    parse_line_cap(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    /// A command line holding just the heart options.
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        heart: HeartArgs,
+    }
+
+    /// Returns the heart options of a command line.
+    fn heart_args(args: &[&str]) -> HeartArgs {
+        Cli::parse_from(std::iter::once("heart").chain(args.iter().copied())).heart
+    }
+
+    /// Parses and validates a TOML file's contents, as [`Config::load`] does.
+    fn parse(text: &str) -> Result<Config, ConfigError> {
+        let config: Config =
+            toml::from_str(text).map_err(|err| ConfigError::Parse(err.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn empty_files_set_nothing() {
+        let config = parse("").unwrap();
+        assert_eq!(config.heart.bpm, None);
+        assert_eq!(config.window.title, None);
+        let args = heart_args(&[]);
+        assert_eq!(config.heart_args(&args, |_| false).bpm, args.bpm);
+    }
+
+    #[test]
+    fn values_out_of_range_are_invalid() {
+        for text in [
+            "[window]\nwidth = 0",
+            "[heart]\nbpm = 0",
+            "[heart]\nbpm = nan",
+            "[heart]\namplitude = -0.1",
+            "[heart]\nstroke_width = -1",
+            "[heart]\ndash = [0, 0]",
+            "[heart.shape]\ntip_sharpness = 2",
+            "[[hearts]]\nphase = nan",
+            "[[hearts]]\nframe = [0.5, 0, 0.6, 1]",
+        ] {
+            assert!(
+                matches!(parse(text), Err(ConfigError::Invalid(_))),
+                "{:?} should be invalid",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn errors_name_the_heart() {
+        let text = "[[hearts]]\n[[hearts]]\nlabel = \"Ward 2\"\nbpm = 0";
+        match parse(text) {
+            Err(ConfigError::Invalid(message)) => {
+                assert!(message.contains("hearts[1].bpm"), "{}", message);
+                assert!(message.contains("Ward 2"), "{}", message);
+            }
+            other => panic!("expected an invalid value, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_keys_and_malformed_values_fail_to_parse() {
+        for text in [
+            "[heart]\nspeed = 2",
+            "[heart]\nenvelope = \"square\"",
+            "[heart]\nfill = \"red\"",
+        ] {
+            assert!(
+                matches!(parse(text), Err(ConfigError::Parse(_))),
+                "{:?} should not parse",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn kept_options_take_precedence_over_the_file() {
+        let config = parse(
+            "[heart]\nbpm = 90\namplitude = 0.2\nenvelope = \"sine\"\nbackground = \"#ffffff\"",
+        )
+        .unwrap();
+        let args = heart_args(&["--bpm", "60", "--envelope", "systolic"]);
+        let args = config.heart_args(&args, |name| name == "bpm" || name == "envelope");
+        assert_eq!(args.bpm, 60.0);
+        assert_eq!(args.envelope, EnvelopeProfile::Systolic);
+        assert_eq!(args.amplitude, 0.2);
+        assert_eq!(args.background, Some(Color::WHITE));
+    }
+}
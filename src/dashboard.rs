@@ -0,0 +1,315 @@
+//! Several hearts side by side.
+//!
+//! A dashboard shows a list of [`HeartEntry`]s, each with its own label,
+//! color and [`AppState`], so every heart beats at its own rate and keeps
+//! its own phase, e.g. one per patient or athlete. [`DashboardWidget`]
+//! arranges them in a grid; hearts given a frame of their own are placed in
+//! it instead, which allows free layouts.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use druid::{
+    kurbo::Rect,
+    widget::{Flex, Label},
+    BoxConstraints, Color, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Size, UpdateCtx, Widget, WidgetExt, WidgetPod,
+};
+
+use crate::beat;
+use crate::color::parse_color;
+use crate::paint::{HeartPainter, DEFAULT_FILL};
+use crate::state::AppState;
+use crate::theme::Themed;
+use crate::widget::{HeartWidget, SET_PAINTER};
+
+/// The hearts shown by a [`DashboardWidget`].
+pub type Hearts = Arc<Vec<HeartEntry>>;
+
+/// One heart of a dashboard.
+#[derive(Clone, Debug, Data)]
+pub struct HeartEntry {
+    /// The name shown below the heart.
+    pub label: Arc<str>,
+    /// The color the heart is filled with.
+    pub fill: Color,
+    /// The heart's own animation model.
+    pub state: AppState,
+    /// Where to place the heart, as fractions of the dashboard's width and
+    /// height; `None` puts it in the next free cell of the grid.
+    pub frame: Option<Rect>,
+}
+
+impl HeartEntry {
+    /// Creates a red heart in the grid beating at `bpm`.
+    pub fn new(label: impl Into<Arc<str>>, bpm: f64) -> Self {
+        HeartEntry {
+            label: label.into(),
+            fill: DEFAULT_FILL,
+            state: AppState::new(bpm, beat::DEFAULT_AMPLITUDE),
+            frame: None,
+        }
+    }
+
+    /// Sets the color the heart is filled with.
+    pub fn fill(mut self, color: Color) -> Self {
+        self.fill = color;
+        self
+    }
+
+    /// Sets the relative size change at the peak of a beat.
+    pub fn amplitude(mut self, amplitude: f64) -> Self {
+        self.state.amplitude = beat::clamp_amplitude(amplitude);
+        self
+    }
+
+    /// Starts the heart at a phase within its beat, from 0.0 to 1.0, so
+    /// hearts beating at the same rate need not beat in unison.
+    pub fn phase(mut self, phase: f64) -> Self {
+        self.state.beats = phase.rem_euclid(1.0);
+        self
+    }
+
+    /// Places the heart in a frame given as fractions of the dashboard's
+    /// width and height, e.g. `Rect::new(0.0, 0.0, 0.5, 1.0)` for the left
+    /// half.
+    pub fn frame(mut self, frame: Rect) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+}
+
+/// Parses `LABEL:BPM` or `LABEL:BPM:COLOR`, such as `Alice:62:#3080ff`.
+impl FromStr for HeartEntry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let (Some(label), Some(bpm)) = (parts.next(), parts.next()) else {
+            return Err(format!(
+                "`{}` is not a heart; expected LABEL:BPM or LABEL:BPM:COLOR",
+                s
+            ));
+        };
+        let bpm = match bpm.trim().parse::<f64>() {
+            Ok(bpm) if (beat::MIN_BPM..=beat::MAX_BPM).contains(&bpm) => bpm,
+            _ => {
+                return Err(format!(
+                    "`{}` is not a heart rate between {} and {}",
+                    bpm,
+                    beat::MIN_BPM,
+                    beat::MAX_BPM
+                ))
+            }
+        };
+        let entry = HeartEntry::new(label.trim(), bpm);
+        match parts.next() {
+            Some(color) => Ok(entry.fill(parse_color(color.trim())?)),
+            None => Ok(entry),
+        }
+    }
+}
+
+/// A widget showing several hearts, each labelled with its name and rate.
+pub struct DashboardWidget {
+    /// Describes every heart apart from its fill color.
+    painter: HeartPainter,
+    /// Which parts of the painter follow the [`Env`]'s theme.
+    themed: Themed,
+    /// Number of grid columns; `None` picks a near-square grid.
+    columns: Option<usize>,
+    children: Vec<WidgetPod<HeartEntry, Box<dyn Widget<HeartEntry>>>>,
+}
+
+impl DashboardWidget {
+    /// Creates a dashboard drawing its hearts like `painter`, apart from
+    /// their fill colors, with the parts `themed` names following the
+    /// [`Env`]'s theme as [`HeartBuilder::themed`] returns them.
+    ///
+    /// [`HeartBuilder::themed`]: crate::HeartBuilder::themed
+    pub fn new(painter: HeartPainter, themed: Themed) -> Self {
+        DashboardWidget {
+            painter,
+            themed,
+            columns: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Sets the number of grid columns. Without one, the grid is as close to
+    /// square as the number of hearts allows.
+    pub fn columns(mut self, columns: usize) -> Self {
+        self.columns = Some(columns.max(1));
+        self
+    }
+
+    /// Creates one child per heart.
+    fn rebuild(&mut self, hearts: &Hearts) {
+        self.children = hearts
+            .iter()
+            .map(|_| WidgetPod::new(tile(self.painter.clone(), self.themed).boxed()))
+            .collect();
+    }
+
+    /// Returns the rectangle of every heart on a dashboard of the given size.
+    fn frames(&self, hearts: &Hearts, size: Size) -> Vec<Rect> {
+        let in_grid = hearts.iter().filter(|entry| entry.frame.is_none()).count();
+        let columns = self
+            .columns
+            .unwrap_or_else(|| (in_grid as f64).sqrt().ceil() as usize)
+            .max(1);
+        let rows = in_grid.div_ceil(columns).max(1);
+        let cell = Size::new(size.width / columns as f64, size.height / rows as f64);
+
+        let mut next = 0;
+        hearts
+            .iter()
+            .map(|entry| match entry.frame {
+                Some(frame) => Rect::new(
+                    frame.x0 * size.width,
+                    frame.y0 * size.height,
+                    frame.x1 * size.width,
+                    frame.y1 * size.height,
+                ),
+                None => {
+                    let (row, column) = (next / columns, next % columns);
+                    next += 1;
+                    Rect::from_origin_size(
+                        (column as f64 * cell.width, row as f64 * cell.height),
+                        cell,
+                    )
+                }
+            })
+            .collect()
+    }
+}
+
+/// Builds the widget showing one heart with its label and rate.
+fn tile(painter: HeartPainter, themed: Themed) -> impl Widget<HeartEntry> {
+    let heart = EntryHeart(HeartWidget::from_painter(painter, themed));
+    let label = Label::dynamic(|entry: &HeartEntry, _env: &Env| {
+        format!("{} · {:.0} BPM", entry.label, entry.state.bpm)
+    });
+    Flex::column()
+        .with_flex_child(heart, 1.0)
+        .with_child(label.padding(4.0))
+}
+
+/// A [`HeartWidget`] animating the state of a [`HeartEntry`] in its color.
+struct EntryHeart(HeartWidget);
+
+impl Widget<HeartEntry> for EntryHeart {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut HeartEntry, env: &Env) {
+        self.0.event(ctx, event, &mut data.state, env);
+        // A new painter brings a fill color of its own
+        if let Event::Command(cmd) = event {
+            if cmd.is(SET_PAINTER) {
+                self.0.set_fill(data.fill);
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &HeartEntry,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            self.0.set_fill(data.fill);
+        }
+        self.0.lifecycle(ctx, event, &data.state, env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &HeartEntry, data: &HeartEntry, env: &Env) {
+        if !old_data.fill.same(&data.fill) {
+            self.0.set_fill(data.fill);
+            ctx.request_paint();
+        }
+        self.0.update(ctx, &old_data.state, &data.state, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &HeartEntry,
+        env: &Env,
+    ) -> Size {
+        self.0.layout(ctx, bc, &data.state, env)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &HeartEntry, env: &Env) {
+        self.0.paint(ctx, &data.state, env);
+    }
+}
+
+impl Widget<Hearts> for DashboardWidget {
+    /// Passes events on to every heart, each with its own entry. A
+    /// [`SET_PAINTER`] command also changes how hearts added later are
+    /// drawn.
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut Hearts, env: &Env) {
+        if let Event::Command(cmd) = event {
+            if let Some((painter, themed)) = cmd.get(SET_PAINTER) {
+                self.painter = painter.clone();
+                self.themed = *themed;
+            }
+        }
+        for (index, child) in self.children.iter_mut().enumerate() {
+            let Some(entry) = data.get(index) else {
+                break;
+            };
+            let mut entry = entry.clone();
+            child.event(ctx, event, &mut entry, env);
+            if !entry.same(&data[index]) {
+                Arc::make_mut(data)[index] = entry;
+            }
+        }
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &Hearts, env: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            self.rebuild(data);
+        }
+        for (child, entry) in self.children.iter_mut().zip(data.iter()) {
+            child.lifecycle(ctx, event, entry, env);
+        }
+    }
+
+    /// Rebuilds the hearts when entries were added or removed, and updates
+    /// them otherwise.
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &Hearts, data: &Hearts, env: &Env) {
+        if old_data.len() != data.len() {
+            self.rebuild(data);
+            ctx.children_changed();
+            return;
+        }
+        for (child, entry) in self.children.iter_mut().zip(data.iter()) {
+            child.update(ctx, entry, env);
+        }
+    }
+
+    /// Fills the available space and lays the hearts out in it.
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &Hearts,
+        env: &Env,
+    ) -> Size {
+        let size = bc.max();
+        let frames = self.frames(data, size);
+        for ((child, entry), frame) in self.children.iter_mut().zip(data.iter()).zip(frames) {
+            child.layout(ctx, &BoxConstraints::tight(frame.size()), entry, env);
+            child.set_origin(ctx, frame.origin());
+        }
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &Hearts, env: &Env) {
+        for (child, entry) in self.children.iter_mut().zip(data.iter()) {
+            child.paint(ctx, entry, env);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! An electrocardiogram (ECG) strip beating in step with the heart.
+//!
+//! [`EcgWaveform`] synthesizes one heartbeat's trace from the same beat
+//! phase that drives the heart, and [`EcgWidget`] scrolls that trace across
+//! the screen. The waveform's R peak is placed at the envelope's strongest
+//! contraction, so the spike on the strip and the heart's swell coincide.
+
+use druid::{
+    kurbo::{BezPath, Circle, Line, Point},
+    piet::{Color, RenderContext},
+    BoxConstraints, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Size,
+    UpdateCtx, Widget,
+};
+
+use crate::beat;
+use crate::envelope::{self, pulse, BeatEnvelope, EnvelopeProfile};
+use crate::state::AppState;
+use crate::widget::SET_PAINTER;
+
+/// Trace color used unless configured otherwise.
+pub const DEFAULT_TRACE: Color = Color::rgb8(0, 200, 80);
+/// Grid color used unless configured otherwise.
+pub const DEFAULT_GRID: Color = Color::rgba8(0, 200, 80, 48);
+/// Length of the visible strip used unless configured otherwise, in seconds.
+pub const DEFAULT_DURATION: f64 = 3.0;
+
+/// Preferred height of the strip.
+const STRIP_HEIGHT: f64 = 120.0;
+/// Width used when the parent leaves the width unbounded.
+const FALLBACK_WIDTH: f64 = 400.0;
+/// Time between grid lines, in seconds; 0.2 s matches a large ECG box.
+const GRID_STEP: f64 = 0.2;
+/// Height of the trace's highest point, the R peak, as a fraction of the
+/// strip's height.
+const PEAK_HEIGHT: f64 = 0.6;
+/// Vertical position of the baseline, as a fraction of the strip's height.
+const BASELINE: f64 = 0.7;
+/// Radius of the dot marking the newest point of the trace.
+const CURSOR_RADIUS: f64 = 3.0;
+
+/// The waves making up one heartbeat, as (offset from the R peak, height,
+/// width); offsets and widths are fractions of the beat.
+const WAVES: [(f64, f64, f64); 5] = [
+    // P wave: atrial depolarization
+    (-0.16, 0.12, 0.025),
+    // QRS complex: ventricular depolarization
+    (-0.025, -0.12, 0.008),
+    (0.0, 1.0, 0.009),
+    (0.025, -0.25, 0.01),
+    // T wave: ventricular repolarization
+    (0.24, 0.3, 0.04),
+];
+
+/// A synthetic ECG trace for a single heartbeat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EcgWaveform {
+    /// The phase within the beat at which the R peak occurs.
+    pub r_peak: f64,
+}
+
+impl EcgWaveform {
+    /// Creates a waveform whose R peak coincides with the envelope's
+    /// strongest contraction.
+    pub fn aligned_to(envelope: &dyn BeatEnvelope) -> Self {
+        EcgWaveform {
+            r_peak: envelope::peak_phase(envelope),
+        }
+    }
+
+    /// Returns the trace's height at `phase`, relative to an R peak of 1.0.
+    pub fn value(&self, phase: f64) -> f64 {
+        WAVES
+            .iter()
+            .map(|&(offset, height, width)| height * pulse(phase, self.r_peak + offset, width))
+            .sum()
+    }
+}
+
+impl Default for EcgWaveform {
+    fn default() -> Self {
+        EcgWaveform::aligned_to(&EnvelopeProfile::default())
+    }
+}
+
+/// A widget drawing a scrolling ECG strip from an [`AppState`].
+///
+/// The newest point of the trace is at the right edge and older beats scroll
+/// off to the left. The widget only reads the state; place it next to the
+/// [`HeartWidget`] that advances it. When it receives a [`SET_PAINTER`]
+/// command it realigns its R peak to the new painter's envelope.
+///
+/// [`HeartWidget`]: crate::HeartWidget
+pub struct EcgWidget {
+    waveform: EcgWaveform,
+    trace: Color,
+    grid: Option<Color>,
+    line_width: f64,
+    duration: f64,
+}
+
+impl EcgWidget {
+    /// Creates a strip aligned to the default beat envelope.
+    pub fn new() -> Self {
+        EcgWidget {
+            waveform: EcgWaveform::default(),
+            trace: DEFAULT_TRACE,
+            grid: Some(DEFAULT_GRID),
+            line_width: 2.0,
+            duration: DEFAULT_DURATION,
+        }
+    }
+
+    /// Aligns the R peak to the strongest contraction of an envelope.
+    pub fn align_to(mut self, envelope: &dyn BeatEnvelope) -> Self {
+        self.waveform = EcgWaveform::aligned_to(envelope);
+        self
+    }
+
+    /// Sets the color of the trace.
+    pub fn trace(mut self, color: Color) -> Self {
+        self.trace = color;
+        self
+    }
+
+    /// Sets the color of the grid; `None` hides it.
+    pub fn grid(mut self, color: Option<Color>) -> Self {
+        self.grid = color;
+        self
+    }
+
+    /// Sets the width of the trace.
+    pub fn line_width(mut self, width: f64) -> Self {
+        self.line_width = width.max(0.0);
+        self
+    }
+
+    /// Sets the length of time the strip shows, in seconds.
+    pub fn duration(mut self, seconds: f64) -> Self {
+        if seconds > 0.0 {
+            self.duration = seconds;
+        }
+        self
+    }
+
+    /// Returns the trace for the visible stretch of time.
+    ///
+    /// Without a timeline, past beats are reconstructed at the current heart
+    /// rate, so the whole strip redraws at the new speed when the rate
+    /// changes.
+    fn trace_path(&self, size: Size, state: &AppState) -> BezPath {
+        let columns = size.width.ceil().max(1.0) as usize;
+
+        let mut path = BezPath::new();
+        for column in 0..=columns {
+            let x = column as f64;
+            let age = self.duration * (1.0 - x / size.width);
+            let beats = state.beats_at(state.time - age);
+            let y = self.trace_y(size, beat::phase(beats));
+            if column == 0 {
+                path.move_to((x, y));
+            } else {
+                path.line_to((x, y));
+            }
+        }
+        path
+    }
+
+    /// Returns the vertical position of the trace at a phase of the beat.
+    fn trace_y(&self, size: Size, phase: f64) -> f64 {
+        size.height * (BASELINE - PEAK_HEIGHT * self.waveform.value(phase))
+    }
+}
+
+impl Default for EcgWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Widget<AppState> for EcgWidget {
+    /// Handles events for the EcgWidget.
+    ///
+    /// Only [`SET_PAINTER`] commands are processed, realigning the R peak to
+    /// the heart's new envelope.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The event context used to request painting.
+    /// * `event` - The event being handled.
+    /// * `_data` - The application state, which is currently unused.
+    /// * `_env` - The environment, which is currently unused.
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut AppState, _env: &Env) {
+        if let Event::Command(cmd) = event {
+            if let Some((painter, _)) = cmd.get(SET_PAINTER) {
+                self.waveform = EcgWaveform::aligned_to(&*painter.envelope);
+                ctx.request_paint();
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &AppState,
+        _env: &Env,
+    ) {
+    }
+
+    /// Repaints the strip whenever the animation moves on.
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppState, data: &AppState, _env: &Env) {
+        if old_data.beats != data.beats || old_data.bpm != data.bpm {
+            ctx.request_paint();
+        }
+    }
+
+    /// Computes the preferred size of the EcgWidget.
+    ///
+    /// The strip takes the full available width and a fixed height.
+    ///
+    /// # Returns
+    ///
+    /// The preferred size of the widget.
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &AppState,
+        _env: &Env,
+    ) -> Size {
+        let width = if bc.is_width_bounded() {
+            bc.max().width
+        } else {
+            FALLBACK_WIDTH
+        };
+        bc.constrain((width, STRIP_HEIGHT))
+    }
+
+    /// Paints the grid, the trace and a dot at the trace's newest point.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The painting context used to draw the strip.
+    /// * `data` - The application state, which provides the beat position and
+    ///   heart rate.
+    /// * `_env` - The environment, which is currently unused.
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppState, _env: &Env) {
+        let size = ctx.size();
+        // Nothing to draw on, and the grid and trace would divide by zero
+        if size.width <= 0.0 || size.height <= 0.0 {
+            return;
+        }
+
+        if let Some(grid) = self.grid {
+            // Vertical lines stay fixed to moments in time as they scroll by
+            let step = size.width * GRID_STEP / self.duration;
+            let offset = (data.time / GRID_STEP).fract() * step;
+            let mut x = size.width - offset;
+            while x >= 0.0 {
+                ctx.stroke(Line::new((x, 0.0), (x, size.height)), &grid, 1.0);
+                x -= step;
+            }
+            let baseline = size.height * BASELINE;
+            ctx.stroke(
+                Line::new((0.0, baseline), (size.width, baseline)),
+                &grid,
+                1.0,
+            );
+        }
+
+        let path = self.trace_path(size, data);
+        ctx.stroke(&path, &self.trace, self.line_width);
+
+        let cursor = Point::new(size.width, self.trace_y(size, data.phase()));
+        ctx.fill(Circle::new(cursor, CURSOR_RADIUS), &self.trace);
+    }
+}
@@ -0,0 +1,232 @@
+//! Beat envelopes: how the heart's size evolves over the course of one beat.
+//!
+//! An envelope maps the phase within a beat to the heart's displacement from
+//! its resting size. The beat amplitude then scales that displacement, so a
+//! value of 1.0 at the peak grows the heart by exactly the amplitude.
+
+use std::f64::consts::{PI, TAU};
+use std::fmt;
+use std::str::FromStr;
+
+/// Phase at which the first heart sound peaks.
+const LUB_CENTER: f64 = 0.10;
+/// Width of the first heart sound's pulse, as a fraction of the beat.
+const LUB_WIDTH: f64 = 0.045;
+/// Phase at which the second heart sound peaks.
+const DUB_CENTER: f64 = 0.36;
+/// Width of the second heart sound's pulse, as a fraction of the beat.
+const DUB_WIDTH: f64 = 0.035;
+/// Strength of the second heart sound relative to the first.
+const DUB_STRENGTH: f64 = 0.6;
+
+/// Fraction of the beat spent contracting in the systolic profile.
+const SYSTOLE: f64 = 0.12;
+/// Decay rate of the diastolic relaxation; higher values relax faster.
+const DIASTOLE_DECAY: f64 = 5.0;
+
+/// Maps the phase within a beat to a displacement from the resting size.
+///
+/// Implementations should return 0.0 at rest and 1.0 at the strongest point of
+/// the beat; negative values shrink the heart below its resting size. Any
+/// `Fn(f64) -> f64` closure can be used as an envelope.
+pub trait BeatEnvelope: Send + Sync {
+    /// Returns the displacement at `phase`, which lies in `[0, 1)`.
+    fn value(&self, phase: f64) -> f64;
+}
+
+impl<F> BeatEnvelope for F
+where
+    F: Fn(f64) -> f64 + Send + Sync,
+{
+    fn value(&self, phase: f64) -> f64 {
+        self(phase)
+    }
+}
+
+/// The built-in beat envelopes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EnvelopeProfile {
+    /// A plain sine wave, swelling and shrinking evenly around the rest size.
+    Sine,
+    /// The double thump of the first and second heart sounds: a strong "lub"
+    /// as the ventricles contract, followed by a softer "dub" as the valves
+    /// close.
+    #[default]
+    LubDub,
+    /// A sharp systolic contraction followed by a slow diastolic relaxation.
+    Systolic,
+}
+
+impl EnvelopeProfile {
+    /// Every built-in profile, in the order they are presented to users.
+    pub const ALL: [EnvelopeProfile; 3] = [
+        EnvelopeProfile::LubDub,
+        EnvelopeProfile::Systolic,
+        EnvelopeProfile::Sine,
+    ];
+
+    /// Returns the name used to select the profile in options and files.
+    pub fn name(self) -> &'static str {
+        match self {
+            EnvelopeProfile::Sine => "sine",
+            EnvelopeProfile::LubDub => "lub-dub",
+            EnvelopeProfile::Systolic => "systolic",
+        }
+    }
+}
+
+impl BeatEnvelope for EnvelopeProfile {
+    fn value(&self, phase: f64) -> f64 {
+        match self {
+            EnvelopeProfile::Sine => f64::sin(phase * TAU),
+            EnvelopeProfile::LubDub => {
+                pulse(phase, LUB_CENTER, LUB_WIDTH)
+                    + DUB_STRENGTH * pulse(phase, DUB_CENTER, DUB_WIDTH)
+            }
+            EnvelopeProfile::Systolic => systolic(phase),
+        }
+    }
+}
+
+impl fmt::Display for EnvelopeProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for EnvelopeProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EnvelopeProfile::ALL
+            .into_iter()
+            .find(|profile| profile.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = EnvelopeProfile::ALL.iter().map(|p| p.name()).collect();
+                format!(
+                    "unknown beat envelope `{}` (expected one of: {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Number of phases [`peak_phase`] samples per beat.
+const PEAK_SAMPLES: usize = 1000;
+
+/// Returns the phase at which an envelope reaches its largest value, i.e.
+/// the moment of strongest contraction.
+///
+/// The envelope is sampled, so the result is accurate to a thousandth of a
+/// beat.
+pub fn peak_phase(envelope: &dyn BeatEnvelope) -> f64 {
+    (0..PEAK_SAMPLES)
+        .map(|i| i as f64 / PEAK_SAMPLES as f64)
+        .fold((0.0, f64::NEG_INFINITY), |(best, best_value), phase| {
+            let value = envelope.value(phase);
+            if value > best_value {
+                (phase, value)
+            } else {
+                (best, best_value)
+            }
+        })
+        .0
+}
+
+/// Returns the phase at which the heart has relaxed halfway from its
+/// strongest contraction, taken as the start of diastole.
+///
+/// Like [`peak_phase`], the result is accurate to a thousandth of a beat. An
+/// envelope that never relaxes that far yields its peak phase.
+pub fn diastole_phase(envelope: &dyn BeatEnvelope) -> f64 {
+    let peak = peak_phase(envelope);
+    let half = envelope.value(peak) / 2.0;
+    (1..PEAK_SAMPLES)
+        .map(|i| (peak + i as f64 / PEAK_SAMPLES as f64).rem_euclid(1.0))
+        .find(|&phase| envelope.value(phase) <= half)
+        .unwrap_or(peak)
+}
+
+/// A Gaussian bump of height 1.0 centred on `center`, wrapping around the
+/// ends of the beat so consecutive beats join smoothly.
+pub(crate) fn pulse(phase: f64, center: f64, width: f64) -> f64 {
+    let distance = (phase - center + 0.5).rem_euclid(1.0) - 0.5;
+    f64::exp(-0.5 * (distance / width).powi(2))
+}
+
+/// A quick eased rise to 1.0 followed by an exponential fall that reaches
+/// exactly 0.0 at the end of the beat.
+fn systolic(phase: f64) -> f64 {
+    if phase < SYSTOLE {
+        0.5 - 0.5 * f64::cos(PI * phase / SYSTOLE)
+    } else {
+        let x = (phase - SYSTOLE) / (1.0 - SYSTOLE);
+        let floor = f64::exp(-DIASTOLE_DECAY);
+        (f64::exp(-DIASTOLE_DECAY * x) - floor) / (1.0 - floor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns `true` if two displacements are equal up to rounding.
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn sine_swells_and_shrinks_evenly() {
+        let sine = EnvelopeProfile::Sine;
+        assert!(close(sine.value(0.0), 0.0));
+        assert!(close(sine.value(0.25), 1.0));
+        assert!(close(sine.value(0.75), -1.0));
+    }
+
+    #[test]
+    fn lub_dub_thumps_twice() {
+        let lub_dub = EnvelopeProfile::LubDub;
+        let lub = lub_dub.value(LUB_CENTER);
+        let dub = lub_dub.value(DUB_CENTER);
+        assert!(close(lub, 1.0));
+        assert!(close(dub, DUB_STRENGTH));
+        // The heart rests between the thumps and after the second one
+        let gap = lub_dub.value((LUB_CENTER + DUB_CENTER) / 2.0);
+        assert!(gap < 0.1 * dub);
+        assert!(lub_dub.value(0.7) < 1e-6);
+    }
+
+    #[test]
+    fn systolic_contracts_quickly_and_relaxes_to_rest() {
+        let systolic = EnvelopeProfile::Systolic;
+        assert!(close(systolic.value(0.0), 0.0));
+        assert!(close(systolic.value(SYSTOLE), 1.0));
+        assert!(close(systolic.value(1.0), 0.0));
+        let relaxation: Vec<f64> = (1..=10)
+            .map(|i| systolic.value(SYSTOLE + (1.0 - SYSTOLE) * i as f64 / 10.0))
+            .collect();
+        assert!(relaxation.windows(2).all(|pair| pair[1] < pair[0]));
+    }
+
+    #[test]
+    fn pulses_wrap_around_the_beat() {
+        assert!(close(pulse(0.95, 0.05, 0.1), pulse(0.15, 0.05, 0.1)));
+    }
+
+    #[test]
+    fn closures_are_envelopes() {
+        let flat = |_: f64| 0.5;
+        let envelope: &dyn BeatEnvelope = &flat;
+        assert_eq!(envelope.value(0.3), 0.5);
+    }
+
+    #[test]
+    fn profiles_parse_their_names() {
+        for profile in EnvelopeProfile::ALL {
+            assert_eq!(profile.to_string().parse(), Ok(profile));
+        }
+        assert_eq!("LUB-DUB".parse(), Ok(EnvelopeProfile::LubDub));
+        assert!("square".parse::<EnvelopeProfile>().is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Events marking the stages of each beat.
+//!
+//! The beat is continuous: the heart's size follows its envelope through
+//! every frame. [`BeatTracker`] picks out the moments an embedding
+//! application cares about — the start of a beat, the strongest contraction
+//! and the start of relaxation — as the animation passes them.
+//! [`HeartWidget`] reports them as [`BEAT_EVENT`] notifications and to the
+//! callbacks registered with [`HeartBuilder::on_beat`].
+//!
+//! [`HeartWidget`]: crate::HeartWidget
+//! [`BEAT_EVENT`]: crate::BEAT_EVENT
+//! [`HeartBuilder::on_beat`]: crate::HeartBuilder::on_beat
+
+use std::fmt;
+
+use crate::envelope::{self, BeatEnvelope};
+use crate::state::AppState;
+
+/// A stage of the beat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BeatEventKind {
+    /// A new beat starts and the heart begins to contract.
+    Onset,
+    /// The heart reaches its strongest contraction.
+    Systole,
+    /// The heart has relaxed halfway and refills.
+    Diastole,
+}
+
+impl BeatEventKind {
+    /// Every stage, in the order they occur within a beat.
+    pub const ALL: [BeatEventKind; 3] = [
+        BeatEventKind::Onset,
+        BeatEventKind::Systole,
+        BeatEventKind::Diastole,
+    ];
+
+    /// Returns the name of the stage.
+    pub fn name(self) -> &'static str {
+        match self {
+            BeatEventKind::Onset => "onset",
+            BeatEventKind::Systole => "systole",
+            BeatEventKind::Diastole => "diastole",
+        }
+    }
+}
+
+impl fmt::Display for BeatEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The animation passed a stage of a beat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeatEvent {
+    /// The stage that was reached.
+    pub kind: BeatEventKind,
+    /// The number of the beat, counting from zero at the start of the
+    /// animation.
+    pub beat: i64,
+    /// The animation time of the frame that passed the stage, in seconds.
+    pub time: f64,
+    /// The heart rate at that frame, in beats per minute.
+    pub bpm: f64,
+}
+
+/// Finds the beat stages the animation passes from frame to frame.
+///
+/// Call [`update`] after every change of the beat position. Only steady
+/// progress produces events: jumping back, or ahead by more than a whole
+/// beat, as seeking does, produces none.
+///
+/// [`update`]: BeatTracker::update
+#[derive(Clone, Debug)]
+pub struct BeatTracker {
+    /// Each stage with the phase at which it occurs.
+    stages: [(BeatEventKind, f64); 3],
+    /// The beat position at the previous update.
+    last: Option<f64>,
+}
+
+impl BeatTracker {
+    /// Creates a tracker for a heart beating with `envelope`.
+    pub fn new(envelope: &dyn BeatEnvelope) -> Self {
+        BeatTracker {
+            stages: stages(envelope),
+            last: None,
+        }
+    }
+
+    /// Times the stages to a different beat envelope.
+    pub fn set_envelope(&mut self, envelope: &dyn BeatEnvelope) {
+        self.stages = stages(envelope);
+    }
+
+    /// Returns the stages passed since the previous update, in the order
+    /// they occurred.
+    pub fn update(&mut self, state: &AppState) -> Vec<BeatEvent> {
+        let beats = state.beats;
+        let Some(last) = self.last.replace(beats) else {
+            return Vec::new();
+        };
+        if beats <= last || beats - last > 1.0 {
+            return Vec::new();
+        }
+
+        let mut passed: Vec<(f64, BeatEvent)> = Vec::new();
+        for &(kind, phase) in &self.stages {
+            let beat = (beats - phase).floor();
+            if beat > (last - phase).floor() {
+                let event = BeatEvent {
+                    kind,
+                    beat: beat as i64,
+                    time: state.time,
+                    bpm: state.bpm,
+                };
+                passed.push((beat + phase, event));
+            }
+        }
+        passed.sort_by(|a, b| a.0.total_cmp(&b.0));
+        passed.into_iter().map(|(_, event)| event).collect()
+    }
+}
+
+/// Returns the phases of the stages of a beat shaped by `envelope`.
+fn stages(envelope: &dyn BeatEnvelope) -> [(BeatEventKind, f64); 3] {
+    [
+        (BeatEventKind::Onset, 0.0),
+        (BeatEventKind::Systole, envelope::peak_phase(envelope)),
+        (BeatEventKind::Diastole, envelope::diastole_phase(envelope)),
+    ]
+}
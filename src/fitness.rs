@@ -0,0 +1,523 @@
+//! Heart-rate data from fitness watch exports.
+//!
+//! Reads the heart rate recorded in Garmin FIT files, Training Center (TCX)
+//! files and GPX tracks with heart-rate extensions, and turns it into a
+//! [`Timeline`]. FIT files that carry beat-to-beat (RR) intervals replay
+//! every recorded beat; otherwise the heart follows the sampled rate.
+
+use crate::timeline::{RateSample, Timeline, TimelineError};
+
+/// FIT global message number of activity records.
+const FIT_RECORD: u16 = 20;
+/// FIT global message number of beat-to-beat intervals.
+const FIT_HRV: u16 = 78;
+/// FIT field number of a record's heart rate.
+const FIT_HEART_RATE: u8 = 3;
+/// FIT field number of an HRV message's intervals.
+const FIT_HRV_TIME: u8 = 0;
+/// FIT field number of timestamps, shared by all messages.
+const FIT_TIMESTAMP: u8 = 253;
+
+/// Parses a FIT activity file.
+///
+/// Beat-to-beat intervals are preferred when the file has them; the beats
+/// start at the first record.
+pub fn parse_fit(data: &[u8]) -> Result<Timeline, TimelineError> {
+    let mut reader = FitReader::new(data)?;
+    let mut samples = Vec::new();
+    let mut intervals = Vec::new();
+    let mut start = None;
+
+    while let Some(message) = reader.next_message()? {
+        match message.global {
+            FIT_RECORD => {
+                let time = message.timestamp.map(f64::from);
+                if start.is_none() {
+                    start = time;
+                }
+                let bpm = message
+                    .field(FIT_HEART_RATE)
+                    .and_then(|values| values.first());
+                if let (Some(time), Some(&bpm)) = (time, bpm) {
+                    samples.push(RateSample {
+                        time,
+                        bpm: bpm as f64,
+                    });
+                }
+            }
+            FIT_HRV => {
+                if let Some(values) = message.field(FIT_HRV_TIME) {
+                    intervals.extend(values.iter().map(|&ms| ms as f64 / 1000.0));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if intervals.len() >= 2 {
+        let mut peak = start.unwrap_or(0.0);
+        let peaks: Vec<f64> = intervals
+            .iter()
+            .map(|interval| {
+                peak += interval;
+                peak
+            })
+            .collect();
+        return Timeline::from_peaks(&peaks);
+    }
+    rate_timeline(samples)
+}
+
+/// Parses a Training Center (TCX) file.
+pub fn parse_tcx(text: &str) -> Result<Timeline, TimelineError> {
+    let document = parse_xml(text)?;
+    let samples = document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "Trackpoint")
+        .filter_map(|point| {
+            let time = child_text(point, "Time").and_then(parse_timestamp)?;
+            let bpm = point
+                .children()
+                .find(|node| node.tag_name().name() == "HeartRateBpm")
+                .and_then(|rate| child_text(rate, "Value"))?
+                .parse()
+                .ok()?;
+            Some(RateSample { time, bpm })
+        })
+        .collect();
+    rate_timeline(samples)
+}
+
+/// Parses a GPX track whose points carry a heart-rate extension, such as
+/// Garmin's `gpxtpx:hr`.
+pub fn parse_gpx(text: &str) -> Result<Timeline, TimelineError> {
+    let document = parse_xml(text)?;
+    let samples = document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "trkpt")
+        .filter_map(|point| {
+            let time = child_text(point, "time").and_then(parse_timestamp)?;
+            let bpm = point
+                .descendants()
+                .find(|node| matches!(node.tag_name().name(), "hr" | "heartrate"))
+                .and_then(|node| node.text())?
+                .trim()
+                .parse()
+                .ok()?;
+            Some(RateSample { time, bpm })
+        })
+        .collect();
+    rate_timeline(samples)
+}
+
+/// Builds a timeline from samples as recorded by a watch.
+///
+/// Samples without a heart rate, as left by a dropped sensor, and samples
+/// sharing a timestamp with an earlier one are skipped.
+fn rate_timeline(samples: Vec<RateSample>) -> Result<Timeline, TimelineError> {
+    let mut kept: Vec<RateSample> = Vec::with_capacity(samples.len());
+    for sample in samples {
+        let later = kept.last().is_none_or(|last| sample.time > last.time);
+        if sample.bpm > 0.0 && later {
+            kept.push(sample);
+        }
+    }
+    match kept.len() {
+        0 => {
+            return Err(TimelineError::Invalid(
+                "the file has no heart-rate data".to_string(),
+            ))
+        }
+        1 => {
+            return Err(TimelineError::Invalid(
+                "the file has a single heart-rate sample; a timeline needs at least two"
+                    .to_string(),
+            ))
+        }
+        _ => {}
+    }
+    Timeline::from_rate(&kept)
+}
+
+/// Parses an XML document, reporting errors as timeline errors.
+fn parse_xml(text: &str) -> Result<roxmltree::Document<'_>, TimelineError> {
+    roxmltree::Document::parse(text).map_err(|err| TimelineError::Parse(err.to_string()))
+}
+
+/// Returns the trimmed text of a node's first child element with the given
+/// local name.
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.tag_name().name() == name)
+        .and_then(|child| child.text())
+        .map(str::trim)
+}
+
+/// Parses an ISO 8601 timestamp such as `2024-05-01T07:30:15.250Z` into
+/// seconds since the Unix epoch.
+fn parse_timestamp(text: &str) -> Option<f64> {
+    let (date, time) = text.split_once(['T', ' '])?;
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: i64 = date_parts.next()?.parse().ok()?;
+    let day: i64 = date_parts.next()?.parse().ok()?;
+
+    // Split off the zone: `Z`, `+hh:mm` or `-hh:mm`; none means UTC
+    let (clock, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(index) => {
+            let (clock, zone) = time.split_at(index);
+            let offset = match zone {
+                "Z" | "z" => 0.0,
+                _ => {
+                    let sign = if zone.starts_with('-') { -1.0 } else { 1.0 };
+                    let digits = &zone[1..];
+                    let (hours, minutes) = match digits.split_once(':') {
+                        Some(parts) => parts,
+                        None => {
+                            // Multi-byte characters cannot be split inside
+                            let split = digits.len().min(2);
+                            (digits.get(..split)?, digits.get(split..)?)
+                        }
+                    };
+                    let hours: f64 = hours.parse().ok()?;
+                    let minutes: f64 = match minutes {
+                        "" => 0.0,
+                        minutes => minutes.parse().ok()?,
+                    };
+                    sign * (hours * 3600.0 + minutes * 60.0)
+                }
+            };
+            (clock, offset)
+        }
+        None => (time, 0.0),
+    };
+
+    let mut clock_parts = clock.splitn(3, ':');
+    let hours: f64 = clock_parts.next()?.parse().ok()?;
+    let minutes: f64 = clock_parts.next()?.parse().ok()?;
+    let seconds: f64 = clock_parts.next()?.parse().ok()?;
+
+    let days = days_from_civil(year, month, day) as f64;
+    Some(days * 86400.0 + hours * 3600.0 + minutes * 60.0 + seconds - offset)
+}
+
+/// Returns the number of days from 1970-01-01 to a date in the proleptic
+/// Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The layout of a FIT data message, as given by its definition message.
+#[derive(Clone, Default)]
+struct FitDefinition {
+    global: u16,
+    big_endian: bool,
+    /// Each field's number, size in bytes and base type.
+    fields: Vec<(u8, u8, u8)>,
+    /// Total size of the developer fields, which are skipped.
+    developer_size: usize,
+}
+
+/// A decoded FIT data message, keeping only integer fields.
+struct FitMessage {
+    global: u16,
+    timestamp: Option<u32>,
+    fields: Vec<(u8, Vec<u32>)>,
+}
+
+impl FitMessage {
+    /// Returns the valid values of a field.
+    fn field(&self, number: u8) -> Option<&[u32]> {
+        self.fields
+            .iter()
+            .find(|(n, _)| *n == number)
+            .map(|(_, values)| values.as_slice())
+    }
+}
+
+/// Reads the messages of a FIT file one after another.
+struct FitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    end: usize,
+    definitions: [Option<FitDefinition>; 16],
+    /// The most recent timestamp, which compressed headers are relative to.
+    timestamp: u32,
+}
+
+impl<'a> FitReader<'a> {
+    /// Checks the file header and positions the reader at the first record.
+    fn new(data: &'a [u8]) -> Result<Self, TimelineError> {
+        let header_size = *data.first().ok_or_else(|| fit_error("the file is empty"))? as usize;
+        if header_size < 12 || data.len() < header_size || &data[8..12] != b".FIT" {
+            return Err(fit_error("the file is not a FIT file"));
+        }
+        let data_size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        Ok(FitReader {
+            data,
+            position: header_size,
+            end: (header_size + data_size).min(data.len()),
+            definitions: Default::default(),
+            timestamp: 0,
+        })
+    }
+
+    /// Returns the next data message, reading any definitions before it.
+    fn next_message(&mut self) -> Result<Option<FitMessage>, TimelineError> {
+        while self.position < self.end {
+            let header = self.take(1)?[0];
+            if header & 0x80 != 0 {
+                // Compressed timestamp header: a data message with a time
+                // offset of up to 31 seconds from the previous timestamp
+                let local = ((header >> 5) & 0x03) as usize;
+                let offset = (header & 0x1f) as u32;
+                let mut timestamp = (self.timestamp & !0x1f) | offset;
+                if offset < self.timestamp & 0x1f {
+                    timestamp += 0x20;
+                }
+                self.timestamp = timestamp;
+                let mut message = self.read_data(local)?;
+                message.timestamp = Some(timestamp);
+                return Ok(Some(message));
+            }
+
+            let local = (header & 0x0f) as usize;
+            if header & 0x40 != 0 {
+                self.read_definition(local, header & 0x20 != 0)?;
+            } else {
+                let message = self.read_data(local)?;
+                if let Some(timestamp) = message.timestamp {
+                    self.timestamp = timestamp;
+                }
+                return Ok(Some(message));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads a definition message for a local message type.
+    fn read_definition(&mut self, local: usize, developer: bool) -> Result<(), TimelineError> {
+        let fixed = self.take(5)?;
+        let big_endian = fixed[1] == 1;
+        let global = if big_endian {
+            u16::from_be_bytes([fixed[2], fixed[3]])
+        } else {
+            u16::from_le_bytes([fixed[2], fixed[3]])
+        };
+        let count = fixed[4] as usize;
+        let fields = self
+            .take(count * 3)?
+            .chunks(3)
+            .map(|field| (field[0], field[1], field[2]))
+            .collect();
+
+        let mut developer_size = 0;
+        if developer {
+            let count = self.take(1)?[0] as usize;
+            developer_size = self
+                .take(count * 3)?
+                .chunks(3)
+                .map(|field| field[1] as usize)
+                .sum();
+        }
+
+        self.definitions[local] = Some(FitDefinition {
+            global,
+            big_endian,
+            fields,
+            developer_size,
+        });
+        Ok(())
+    }
+
+    /// Reads a data message laid out by the definition of a local type.
+    fn read_data(&mut self, local: usize) -> Result<FitMessage, TimelineError> {
+        let definition = self.definitions[local]
+            .clone()
+            .ok_or_else(|| fit_error("a message has no definition"))?;
+
+        let mut message = FitMessage {
+            global: definition.global,
+            timestamp: None,
+            fields: Vec::new(),
+        };
+        for &(number, size, base_type) in &definition.fields {
+            let bytes = self.take(size as usize)?;
+            let values = fit_values(bytes, base_type, definition.big_endian);
+            if number == FIT_TIMESTAMP {
+                message.timestamp = values.first().copied();
+            } else if !values.is_empty() {
+                message.fields.push((number, values));
+            }
+        }
+        self.take(definition.developer_size)?;
+        Ok(message)
+    }
+
+    /// Returns the next `count` bytes.
+    fn take(&mut self, count: usize) -> Result<&'a [u8], TimelineError> {
+        let end = self.position + count;
+        if end > self.end {
+            return Err(fit_error("the file is truncated"));
+        }
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+}
+
+/// Decodes the valid values of an unsigned integer field; other base types
+/// and invalid values yield nothing.
+fn fit_values(bytes: &[u8], base_type: u8, big_endian: bool) -> Vec<u32> {
+    let width = match base_type & 0x1f {
+        // enum, uint8 and uint8z
+        0x00 | 0x02 | 0x0a => 1,
+        // uint16 and uint16z
+        0x04 | 0x0b => 2,
+        // uint32 and uint32z
+        0x06 | 0x0c => 4,
+        _ => return Vec::new(),
+    };
+    let invalid = match (base_type & 0x1f, width) {
+        (0x0a..=0x0c, _) => 0,
+        (_, 1) => 0xff,
+        (_, 2) => 0xffff,
+        _ => 0xffff_ffff,
+    };
+    bytes
+        .chunks_exact(width)
+        .map(|chunk| {
+            let mut value = [0; 4];
+            if big_endian {
+                value[4 - width..].copy_from_slice(chunk);
+                u32::from_be_bytes(value)
+            } else {
+                value[..width].copy_from_slice(chunk);
+                u32::from_le_bytes(value)
+            }
+        })
+        .filter(|&value| value != invalid)
+        .collect()
+}
+
+/// Returns an error describing a malformed FIT file.
+fn fit_error(message: &str) -> TimelineError {
+    TimelineError::Parse(format!("invalid FIT file: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wraps records in a FIT file header, without the trailing CRC.
+    fn fit_file(records: &[u8]) -> Vec<u8> {
+        let mut data = vec![12, 0x10, 0, 0];
+        data.extend_from_slice(&(records.len() as u32).to_le_bytes());
+        data.extend_from_slice(b".FIT");
+        data.extend_from_slice(records);
+        data
+    }
+
+    /// A little-endian definition of local type 0 as records with a
+    /// timestamp and a heart rate.
+    const RECORD_DEFINITION: [u8; 12] = [0x40, 0, 0, 20, 0, 2, 253, 4, 0x86, 3, 1, 0x02];
+
+    /// Returns a record message of local type 0.
+    fn record(timestamp: u32, bpm: u8) -> Vec<u8> {
+        let mut message = vec![0x00];
+        message.extend_from_slice(&timestamp.to_le_bytes());
+        message.push(bpm);
+        message
+    }
+
+    #[test]
+    fn fit_prefers_beat_intervals() {
+        let mut records = RECORD_DEFINITION.to_vec();
+        records.extend(record(1000, 60));
+        // A big-endian HRV definition of local type 1 with two intervals
+        records.extend([0x41, 0, 1, 0, 78, 1, 0, 4, 0x84]);
+        records.extend([0x01, 0x03, 0xe8, 0x01, 0xf4]);
+
+        let timeline = parse_fit(&fit_file(&records)).unwrap();
+        assert_eq!(timeline.duration(), 0.5);
+        assert_eq!(timeline.bpm_at(0.25), 120.0);
+    }
+
+    #[test]
+    fn fit_records_skip_invalid_rates_and_developer_fields() {
+        // Records with a two-byte developer field after the heart rate
+        let mut records = vec![0x60, 0, 0, 20, 0, 2, 253, 4, 0x86, 3, 1, 0x02, 1, 0, 2, 0];
+        for (timestamp, bpm) in [(1000, 60), (1010, 0xff), (1020, 90)] {
+            records.extend(record(timestamp, bpm));
+            records.extend([0xaa, 0xbb]);
+        }
+
+        let timeline = parse_fit(&fit_file(&records)).unwrap();
+        assert_eq!(timeline.duration(), 20.0);
+        assert_eq!(timeline.bpm_at(5.0), 60.0);
+        assert_eq!(timeline.bpm_at(25.0), 90.0);
+    }
+
+    #[test]
+    fn fit_compressed_timestamps_roll_over() {
+        let mut records = RECORD_DEFINITION.to_vec();
+        // 1054 ends in 30 within its 32-second window
+        records.extend(record(1054, 60));
+        // Local type 1 holds just a heart rate
+        records.extend([0x41, 0, 0, 20, 0, 1, 3, 1, 0x02]);
+        // An offset of 2 lies in the next window: 1058
+        records.extend([0x80 | (1 << 5) | 2, 90]);
+
+        let timeline = parse_fit(&fit_file(&records)).unwrap();
+        assert_eq!(timeline.duration(), 4.0);
+        assert_eq!(timeline.bpm_at(1.0), 60.0);
+    }
+
+    #[test]
+    fn fit_rejects_truncated_input() {
+        let mut records = RECORD_DEFINITION.to_vec();
+        records.extend(record(1000, 60));
+        records.extend(record(1001, 62));
+        let data = fit_file(&records);
+
+        for len in 0..data.len() {
+            assert!(parse_fit(&data[..len]).is_err(), "{} bytes", len);
+        }
+        assert!(parse_fit(&data).is_ok());
+    }
+
+    #[test]
+    fn fit_rejects_a_single_sample() {
+        let mut records = RECORD_DEFINITION.to_vec();
+        records.extend(record(1000, 60));
+        assert!(matches!(
+            parse_fit(&fit_file(&records)),
+            Err(TimelineError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn timestamps_with_zones() {
+        let utc = 1_714_548_615.25;
+        assert_eq!(parse_timestamp("2024-05-01T07:30:15.250Z"), Some(utc));
+        assert_eq!(parse_timestamp("2024-05-01 07:30:15.250"), Some(utc));
+        assert_eq!(parse_timestamp("2024-05-01T09:30:15.250+02:00"), Some(utc));
+        assert_eq!(parse_timestamp("2024-05-01T02:30:15.250-05:00"), Some(utc));
+        assert_eq!(parse_timestamp("2024-05-01T09:30:15.250+0200"), Some(utc));
+        assert_eq!(parse_timestamp("2024-05-01"), None);
+        assert_eq!(parse_timestamp("2024-05-01T07:30Z"), None);
+        assert_eq!(parse_timestamp("2024-05-01T07:30:15+0é00"), None);
+    }
+
+    #[test]
+    fn civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+    }
+}
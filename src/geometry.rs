@@ -0,0 +1,103 @@
+//! Resolution-independent heart geometry.
+//!
+//! The heart is built from two mirrored cubic Bézier curves running from the
+//! bottom tip up to the cleft between the lobes. The curves are laid out in a
+//! unit control box and then mapped onto the requested rectangle, so the same
+//! shape can be painted by the widget, exported to files or used for hit
+//! testing at any size.
+
+use druid::{
+    kurbo::{Affine, BezPath, Point, Rect, Shape},
+    Data,
+};
+
+/// Parameters describing the outline of a heart.
+///
+/// The parameters are expressed relative to a control box spanning `-1..1`
+/// horizontally and `0..1` from top to bottom, with the tip at the bottom
+/// centre. The defaults reproduce the classic beating-heart outline.
+#[derive(Clone, Copy, Debug, Data, PartialEq)]
+pub struct HeartShape {
+    /// How far the lobes bulge upwards, usually between 0.0 (flat-topped)
+    /// and 2.0 (tall, round lobes).
+    pub lobe_roundness: f64,
+    /// How pointed the bottom tip is, between 0.0 (rounded) and 1.0 (sharp).
+    pub tip_sharpness: f64,
+    /// How far the cleft between the lobes reaches down, between 0.0 (none)
+    /// and 1.0 (all the way to the tip).
+    pub cleft_depth: f64,
+}
+
+impl HeartShape {
+    /// Returns the heart's outline fitted exactly into `rect`.
+    ///
+    /// The outline is stretched independently along each axis so that its
+    /// bounding box matches `rect`.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` - The rectangle the heart should fill.
+    pub fn path(&self, rect: Rect) -> BezPath {
+        let mut path = self.unit_path();
+        let bounds = path.bounding_box();
+
+        // Guard against degenerate parameters collapsing an axis
+        let sx = if bounds.width() > 0.0 {
+            rect.width() / bounds.width()
+        } else {
+            1.0
+        };
+        let sy = if bounds.height() > 0.0 {
+            rect.height() / bounds.height()
+        } else {
+            1.0
+        };
+
+        path.apply_affine(
+            Affine::translate(rect.origin().to_vec2())
+                * Affine::scale_non_uniform(sx, sy)
+                * Affine::translate(-bounds.origin().to_vec2()),
+        );
+        path
+    }
+
+    /// Builds the outline in control box coordinates.
+    fn unit_path(&self) -> BezPath {
+        let tip = Point::new(0.0, 1.0);
+        let cleft = Point::new(0.0, self.cleft_depth);
+        let tip_control = 1.0 - self.tip_sharpness;
+        let lobe_control = 1.0 - self.lobe_roundness;
+
+        let mut path = BezPath::new();
+
+        // Start at the bottom tip of the heart
+        path.move_to(tip);
+
+        // Left half of the heart
+        path.curve_to(
+            Point::new(-1.0, tip_control),
+            Point::new(-1.0, lobe_control),
+            cleft,
+        );
+
+        // Right half of the heart
+        path.curve_to(
+            Point::new(1.0, lobe_control),
+            Point::new(1.0, tip_control),
+            tip,
+        );
+
+        path.close_path();
+        path
+    }
+}
+
+impl Default for HeartShape {
+    fn default() -> Self {
+        HeartShape {
+            lobe_roundness: 1.0,
+            tip_sharpness: 0.25,
+            cleft_depth: 0.25,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! A druid widget that animates a beating heart.
+//!
+//! [`HeartWidget`] draws the heart and advances its animation on every
+//! animation frame; [`AppState`] holds the animation model it reads and
+//! updates. Use [`HeartWidget::builder`] to configure the widget's size,
+//! colors, shape, [`style`] and beat envelope together with the initial
+//! heart rate. The heart's outline itself is available without a GUI
+//! through [`HeartShape`], and [`shapes`] offers other icons to pulse with
+//! instead. [`render`] draws complete frames without a window,
+//! [`animation`] exports looping beat cycles, [`svg`] writes vector files
+//! and [`config`] loads the options from a file. [`theme`] styles the
+//! heart through druid's `Env` and [`dashboard`] shows several hearts side
+//! by side. [`EcgWidget`] draws an ECG strip in step with the heart and
+//! [`audio`] synthesizes its sounds; [`live`], [`ble`] and [`osc`] connect
+//! it to sensors and show control. [`HeartBuilder::on_beat`] and
+//! [`BEAT_EVENT`] tell the surrounding application when the heart beats:
+//!
+//! ```no_run
+//! use beating_heart::{EnvelopeProfile, HeartWidget};
+//! use druid::{AppLauncher, Color, WindowDesc};
+//!
+//! let builder = HeartWidget::builder()
+//!     .fill(Color::rgb8(200, 30, 60))
+//!     .bpm(60.0)
+//!     .envelope(EnvelopeProfile::Systolic)
+//!     .on_beat(|event| println!("beat {}: {}", event.beat, event.kind));
+//! let state = builder.state();
+//!
+//! AppLauncher::with_window(WindowDesc::new(builder.build()))
+//!     .launch(state)
+//!     .expect("Failed to launch application");
+//! ```
+
+pub mod animation;
+pub mod audio;
+pub mod beat;
+pub mod ble;
+pub mod cli;
+pub mod clock;
+pub mod color;
+pub mod config;
+pub mod dashboard;
+pub mod ecg;
+pub mod envelope;
+pub mod events;
+pub mod fitness;
+pub mod geometry;
+pub mod live;
+pub mod osc;
+pub mod paint;
+pub mod render;
+pub mod shapes;
+mod state;
+pub mod style;
+pub mod svg;
+pub mod theme;
+pub mod timeline;
+mod widget;
+
+pub use clock::{AnimationClock, ClockMode};
+pub use color::ColorRamp;
+pub use ecg::EcgWidget;
+pub use envelope::{BeatEnvelope, EnvelopeProfile};
+pub use events::{BeatEvent, BeatEventKind};
+pub use geometry::HeartShape;
+pub use paint::{HeartPainter, PaintOrder, StrokeAlign, StrokeOptions};
+pub use shapes::{Icon, ShapeLibrary};
+pub use state::AppState;
+pub use style::{Gradient, HeartStyle};
+pub use theme::{Theme, Themed};
+pub use timeline::Timeline;
+pub use widget::{
+    ClickAction, HeartBuilder, HeartWidget, BEAT, BEAT_EVENT, CLICKED, SET_AMPLITUDE, SET_BPM,
+    SET_FILL, SET_PAINTER,
+};
@@ -0,0 +1,470 @@
+//! Live heart-rate input from another process.
+//!
+//! A sensor bridge connects to the application and sends one message per
+//! line, either as text or as JSON:
+//!
+//! | Text       | JSON                  | Meaning                          |
+//! |------------|-----------------------|----------------------------------|
+//! | `bpm 72.5` | `{"bpm": 72.5}`       | The heart rate is now 72.5 BPM.  |
+//! | `72.5`     |                       | Same as `bpm 72.5`.              |
+//! | `beat`     | `{"event": "beat"}`   | A beat happened just now.        |
+//!
+//! Messages travel over TCP, UDP or a Unix socket, chosen by an
+//! [`Endpoint`] such as `tcp://127.0.0.1:7878`. [`listen`] receives them
+//! and [`LiveSender`] sends them, e.g. from the bundled `fake-sensor` tool.
+//! [`app_handler`] passes received messages on to a running application.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+#[cfg(unix)]
+use std::os::unix::{
+    fs::FileTypeExt,
+    net::{UnixListener, UnixStream},
+};
+
+use druid::{ExtEventSink, Target};
+
+use crate::beat;
+use crate::widget::{BEAT, SET_BPM};
+
+/// Largest UDP datagram accepted, in bytes.
+const MAX_DATAGRAM: usize = 4096;
+
+/// A message from a live heart-rate source.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LiveMessage {
+    /// The current heart rate in beats per minute.
+    Bpm(f64),
+    /// A beat happened at the moment the message was sent.
+    Beat,
+}
+
+impl LiveMessage {
+    /// Returns the message in the JSON form of the protocol.
+    pub fn to_json(&self) -> String {
+        match self {
+            LiveMessage::Bpm(bpm) => format!("{{\"bpm\": {}}}", bpm),
+            LiveMessage::Beat => "{\"event\": \"beat\"}".to_string(),
+        }
+    }
+}
+
+/// Formats the message in the text form of the protocol.
+impl fmt::Display for LiveMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LiveMessage::Bpm(bpm) => write!(f, "bpm {}", bpm),
+            LiveMessage::Beat => f.write_str("beat"),
+        }
+    }
+}
+
+/// Parses one line of the protocol, in either form.
+impl FromStr for LiveMessage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let line = s.trim();
+        if line.starts_with('{') {
+            return parse_json(line);
+        }
+
+        let mut words = line.split_whitespace();
+        let message = match (words.next(), words.next(), words.next()) {
+            (Some(word), None, None) if word.eq_ignore_ascii_case("beat") => LiveMessage::Beat,
+            (Some(value), None, None) => LiveMessage::Bpm(parse_bpm(value)?),
+            (Some(word), Some(value), None) if word.eq_ignore_ascii_case("bpm") => {
+                LiveMessage::Bpm(parse_bpm(value)?)
+            }
+            _ => return Err(format!("`{}` is not a heart-rate message", line)),
+        };
+        Ok(message)
+    }
+}
+
+/// Where live messages are exchanged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    /// A TCP socket; each connection is a stream of lines.
+    Tcp(SocketAddr),
+    /// A UDP socket; each datagram holds one or more lines.
+    Udp(SocketAddr),
+    /// A Unix domain stream socket at a file path.
+    Unix(PathBuf),
+}
+
+/// Parses `tcp://HOST:PORT`, `udp://HOST:PORT` or `unix://PATH`.
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, address) = s.split_once("://").ok_or_else(|| {
+            format!(
+                "`{}` is not an endpoint; expected e.g. tcp://127.0.0.1:7878",
+                s
+            )
+        })?;
+        let socket = || {
+            address
+                .parse::<SocketAddr>()
+                .map_err(|err| format!("`{}` is not a socket address: {}", address, err))
+        };
+        match scheme.to_ascii_lowercase().as_str() {
+            "tcp" => Ok(Endpoint::Tcp(socket()?)),
+            "udp" => Ok(Endpoint::Udp(socket()?)),
+            "unix" if !address.is_empty() => Ok(Endpoint::Unix(PathBuf::from(address))),
+            _ => Err(format!(
+                "unknown endpoint `{}` (expected tcp://, udp:// or unix://)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Tcp(address) => write!(f, "tcp://{}", address),
+            Endpoint::Udp(address) => write!(f, "udp://{}", address),
+            Endpoint::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+/// Receives live messages on an endpoint.
+///
+/// The endpoint is bound before returning, so errors such as an address
+/// already in use are reported right away. Messages are then delivered on
+/// a background thread, in the order they arrive; several sources may be
+/// connected at once. Lines that are not valid messages are passed on as
+/// errors. `on_message` returns `false` to stop delivering.
+///
+/// # Arguments
+///
+/// * `endpoint` - Where to listen.
+/// * `on_message` - Called with every received line.
+pub fn listen<F>(endpoint: &Endpoint, mut on_message: F) -> io::Result<thread::JoinHandle<()>>
+where
+    F: FnMut(Result<LiveMessage, String>) -> bool + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    match endpoint {
+        Endpoint::Tcp(address) => {
+            let listener = TcpListener::bind(address)?;
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    read_lines(stream, sender.clone());
+                }
+            });
+        }
+        Endpoint::Udp(address) => {
+            let socket = UdpSocket::bind(address)?;
+            thread::spawn(move || {
+                let mut buffer = [0; MAX_DATAGRAM];
+                while let Ok(length) = socket.recv(&mut buffer) {
+                    let text = String::from_utf8_lossy(&buffer[..length]);
+                    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+                        if sender.send(line.parse()).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+        #[cfg(unix)]
+        Endpoint::Unix(path) => {
+            // A socket file left behind by an earlier run blocks binding
+            let stale = std::fs::metadata(path)
+                .map(|meta| meta.file_type().is_socket())
+                .unwrap_or(false);
+            if stale && UnixStream::connect(path).is_err() {
+                std::fs::remove_file(path)?;
+            }
+            let listener = UnixListener::bind(path)?;
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    read_lines(stream, sender.clone());
+                }
+            });
+        }
+        #[cfg(not(unix))]
+        Endpoint::Unix(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix sockets are not available on this platform",
+            ))
+        }
+    }
+
+    Ok(thread::spawn(move || {
+        for message in receiver {
+            if !on_message(message) {
+                break;
+            }
+        }
+    }))
+}
+
+/// Forwards the messages of one stream connection on a thread of its own.
+fn read_lines(
+    stream: impl Read + Send + 'static,
+    sender: mpsc::Sender<Result<LiveMessage, String>>,
+) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if !line.trim().is_empty() && sender.send(line.parse()).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Sends live messages to an endpoint.
+pub struct LiveSender {
+    transport: Transport,
+    json: bool,
+}
+
+/// The socket a [`LiveSender`] writes to.
+enum Transport {
+    Stream(Box<dyn Write + Send>),
+    Datagram(UdpSocket, SocketAddr),
+}
+
+impl LiveSender {
+    /// Connects to an endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - Where to send the messages.
+    /// * `json` - Whether to send the JSON form instead of the text form.
+    pub fn connect(endpoint: &Endpoint, json: bool) -> io::Result<LiveSender> {
+        let transport = match endpoint {
+            Endpoint::Tcp(address) => Transport::Stream(Box::new(TcpStream::connect(address)?)),
+            Endpoint::Udp(address) => {
+                let local: SocketAddr = if address.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0u16; 8], 0).into()
+                };
+                Transport::Datagram(UdpSocket::bind(local)?, *address)
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(path) => Transport::Stream(Box::new(UnixStream::connect(path)?)),
+            #[cfg(not(unix))]
+            Endpoint::Unix(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Unix sockets are not available on this platform",
+                ))
+            }
+        };
+        Ok(LiveSender { transport, json })
+    }
+
+    /// Sends one message.
+    pub fn send(&mut self, message: &LiveMessage) -> io::Result<()> {
+        let line = if self.json {
+            message.to_json()
+        } else {
+            message.to_string()
+        };
+        match &mut self.transport {
+            Transport::Stream(stream) => {
+                writeln!(stream, "{}", line)?;
+                stream.flush()
+            }
+            Transport::Datagram(socket, address) => {
+                socket.send_to(format!("{}\n", line).as_bytes(), *address)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Estimates the heart rate from the time between beat messages.
+#[derive(Clone, Debug, Default)]
+pub struct BeatRate {
+    last: Option<Instant>,
+}
+
+impl BeatRate {
+    /// Creates an estimator that has not seen a beat yet.
+    pub fn new() -> Self {
+        BeatRate::default()
+    }
+
+    /// Records a beat happening now and returns the rate since the previous
+    /// beat, if that rate is within the range the animation accepts.
+    pub fn beat(&mut self) -> Option<f64> {
+        let now = Instant::now();
+        let previous = self.last.replace(now)?;
+        let bpm = 60.0 / now.duration_since(previous).as_secs_f64();
+        (beat::MIN_BPM..=beat::MAX_BPM)
+            .contains(&bpm)
+            .then_some(bpm)
+    }
+}
+
+/// Returns a handler for [`listen`] or [`ble::spawn`] passing the messages
+/// of a live heart-rate source on to a running application whose data is an
+/// [`AppState`]. Beats are sent as [`BEAT`] commands, rates as [`SET_BPM`]
+/// commands and errors are reported on standard error. The handler returns
+/// `false` once the application has quit.
+///
+/// The rate is estimated from the time between beats until the source sends
+/// rates of its own, which are trusted from then on.
+///
+/// [`AppState`]: crate::AppState
+/// [`ble::spawn`]: crate::ble::spawn
+pub fn app_handler(sink: ExtEventSink) -> impl FnMut(Result<LiveMessage, String>) -> bool {
+    let mut rate = BeatRate::new();
+    let mut sends_rate = false;
+    move |message| {
+        let bpm = match message {
+            Ok(LiveMessage::Bpm(bpm)) => {
+                sends_rate = true;
+                Some(bpm)
+            }
+            Ok(LiveMessage::Beat) => {
+                // Stop listening once the application has quit
+                if sink.submit_command(BEAT, (), Target::Auto).is_err() {
+                    return false;
+                }
+                rate.beat().filter(|_| !sends_rate)
+            }
+            Err(err) => {
+                eprintln!("warning: ignoring live input: {}", err);
+                None
+            }
+        };
+        match bpm {
+            Some(bpm) => sink
+                .submit_command(SET_BPM, beat::clamp_bpm(bpm), Target::Auto)
+                .is_ok(),
+            None => true,
+        }
+    }
+}
+
+/// Parses the JSON form of a message.
+fn parse_json(line: &str) -> Result<LiveMessage, String> {
+    let value: serde_json::Value = serde_json::from_str(line)
+        .map_err(|err| format!("`{}` is not valid JSON: {}", line, err))?;
+    if let Some(bpm) = value.get("bpm") {
+        let bpm = bpm
+            .as_f64()
+            .ok_or_else(|| format!("`{}` is not a heart rate", bpm))?;
+        return check_bpm(bpm).map(LiveMessage::Bpm);
+    }
+    match value.get("event").and_then(|event| event.as_str()) {
+        Some(event) if event.eq_ignore_ascii_case("beat") => Ok(LiveMessage::Beat),
+        _ => Err(format!("`{}` is not a heart-rate message", line)),
+    }
+}
+
+/// Parses a heart rate given as text.
+fn parse_bpm(s: &str) -> Result<f64, String> {
+    let bpm = s
+        .parse::<f64>()
+        .map_err(|_| format!("`{}` is not a heart rate", s))?;
+    check_bpm(bpm)
+}
+
+/// Checks that a heart rate is a positive, finite number.
+fn check_bpm(bpm: f64) -> Result<f64, String> {
+    if bpm.is_finite() && bpm > 0.0 {
+        Ok(bpm)
+    } else {
+        Err(format!("`{}` is not a heart rate", bpm))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_messages() {
+        assert_eq!("beat".parse(), Ok(LiveMessage::Beat));
+        assert_eq!(" BEAT \n".parse(), Ok(LiveMessage::Beat));
+        assert_eq!("bpm 72.5".parse(), Ok(LiveMessage::Bpm(72.5)));
+        assert_eq!("72.5".parse(), Ok(LiveMessage::Bpm(72.5)));
+        for line in [
+            "",
+            "bpm",
+            "bpm 0",
+            "bpm -60",
+            "bpm nan",
+            "bpm 60 70",
+            "beat now",
+        ] {
+            assert!(
+                line.parse::<LiveMessage>().is_err(),
+                "{:?} should not parse",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn json_messages() {
+        assert_eq!(r#"{"bpm": 72.5}"#.parse(), Ok(LiveMessage::Bpm(72.5)));
+        assert_eq!(r#"{"event": "beat"}"#.parse(), Ok(LiveMessage::Beat));
+        for line in [
+            r#"{"bpm": "fast"}"#,
+            r#"{"bpm": -1}"#,
+            r#"{"event": "skip"}"#,
+            "{",
+        ] {
+            assert!(
+                line.parse::<LiveMessage>().is_err(),
+                "{:?} should not parse",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn messages_survive_both_forms() {
+        for message in [LiveMessage::Beat, LiveMessage::Bpm(61.25)] {
+            assert_eq!(message.to_string().parse(), Ok(message));
+            assert_eq!(message.to_json().parse(), Ok(message));
+        }
+    }
+
+    #[test]
+    fn endpoints() {
+        let endpoint: Endpoint = "tcp://127.0.0.1:7878".parse().unwrap();
+        assert_eq!(endpoint, Endpoint::Tcp(([127, 0, 0, 1], 7878).into()));
+        assert_eq!(endpoint.to_string(), "tcp://127.0.0.1:7878");
+        assert_eq!(
+            "UDP://[::1]:9000"
+                .parse::<Endpoint>()
+                .map(|e| e.to_string()),
+            Ok("udp://[::1]:9000".to_string())
+        );
+        assert_eq!(
+            "unix:///tmp/heart.sock".parse(),
+            Ok(Endpoint::Unix(PathBuf::from("/tmp/heart.sock")))
+        );
+        for s in [
+            "127.0.0.1:7878",
+            "tcp://localhost",
+            "unix://",
+            "http://127.0.0.1:80",
+        ] {
+            assert!(s.parse::<Endpoint>().is_err(), "{:?} should not parse", s);
+        }
+    }
+}
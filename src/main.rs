@@ -1,153 +1,292 @@
 /// This example shows how to create a widget that animates a beating heart shape.
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+#[cfg(feature = "audio")]
+use beating_heart::audio::{AudioPlayer, HeartSounds};
+use beating_heart::{
+    beat,
+    ble::{self, MockTransport},
+    cli::{parse_size, HeartArgs},
+    clock::ClockKeys,
+    config::{self, Config},
+    dashboard::{DashboardWidget, HeartEntry},
+    live::{self, Endpoint},
+    osc::{self, OscBeats, OscSender},
+    timeline::Timeline,
+    AppState, EcgWidget,
+};
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches, Parser};
 use druid::{
-    kurbo::{BezPath, Point},
-    piet::{Color, RenderContext},
-    AppLauncher, BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle,
-    LifeCycleCtx, PaintCtx, Size, UpdateCtx, Widget, WindowDesc,
+    widget::{Flex, Label, Slider},
+    AppLauncher, Data, Env, Size, Widget, WidgetExt, WindowDesc,
 };
 
-#[derive(Clone, Data)]
-struct AppState {
-    time: f64,
+/// Key bindings listed at the end of `--help`.
+const KEYS_HELP: &str = "\
+Keys:
+  Space       Pause or resume the animation
+  Up/Down     Speed up or slow down the animation
+  Left/Right  Skip back or ahead through the timeline
+  Home        Restart the timeline
+  F           Toggle fixed-step mode";
+
+/// Show a beating heart in a window.
+#[derive(Parser)]
+#[command(name = "beating_heart", version, after_help = KEYS_HELP)]
+struct Cli {
+    /// Initial window size, as WIDTHxHEIGHT.
+    #[arg(long, value_parser = parse_size, default_value = "400x560")]
+    size: Size,
+    /// Window title.
+    #[arg(long, default_value = "Beating Heart")]
+    title: String,
+    /// Do not play the heart sounds.
+    #[cfg(feature = "audio")]
+    #[arg(long)]
+    mute: bool,
+    /// Heart-rate recording for the heart to replay: CSV, JSON, or a FIT, TCX
+    /// or GPX export from a fitness watch.
+    #[arg(long)]
+    timeline: Option<PathBuf>,
+    /// Follow a live heart-rate source sending to this endpoint, e.g.
+    /// tcp://127.0.0.1:7878, udp://127.0.0.1:7878 or unix:///tmp/heart.sock.
+    #[arg(long, conflicts_with = "timeline")]
+    listen: Option<Endpoint>,
+    /// Follow Bluetooth heart-rate measurements replayed from a file of hex
+    /// payloads, or from standard input for `-`.
+    #[arg(long, conflicts_with_all = ["timeline", "listen"])]
+    ble_mock: Option<PathBuf>,
+    /// Accept OSC messages setting the heart rate, amplitude and color on
+    /// this UDP address, e.g. 0.0.0.0:9000.
+    #[arg(long, value_name = "ADDRESS")]
+    osc_listen: Option<SocketAddr>,
+    /// Send an OSC message to this UDP address on every beat, e.g.
+    /// 127.0.0.1:9001.
+    #[arg(long, value_name = "ADDRESS")]
+    osc_send: Option<SocketAddr>,
+    /// Show a dashboard with one heart per occurrence, given as LABEL:BPM or
+    /// LABEL:BPM:COLOR, e.g. `--heart Alice:62:#3080ff --heart Bob:95`.
+    #[arg(long = "heart", value_name = "HEART")]
+    hearts: Vec<HeartEntry>,
+    /// Number of grid columns of a dashboard; by default the grid is about
+    /// square.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    columns: Option<u32>,
+    /// TOML or JSON file with further options, reloaded whenever it changes.
+    /// Options given on the command line take precedence over the file.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    heart: HeartArgs,
 }
 
-struct HeartWidget;
-
-impl Widget<AppState> for HeartWidget {
-    /// Handles events for the HeartWidget.
-    ///
-    /// In particular, it processes animation frame events to update the
-    /// animation time and request the next animation frame and repaint.
-    ///
-    /// # Arguments
-    /// 
-    /// * `ctx` - The event context used to request animation frames and painting.
-    /// * `event` - The event being handled. Only `AnimFrame` events are processed.
-    /// * `data` - The application state, which holds the current animation time.
-    /// * `_env` - The environment, which is currently unused.
-    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut AppState, _env: &Env) {
-        // Check if the event is an animation frame event
-        if let Event::AnimFrame(_) = event {
-            // Increment the animation time
-            data.time += 0.016;
-
-            // Request the next animation frame
-            ctx.request_anim_frame();
-
-            // Request a repaint to update the display
-            ctx.request_paint();
-        }
+/// Builds the window content: the heart above its ECG strip and a row of
+/// beat controls.
+fn build_ui(heart: impl Widget<AppState> + 'static, ecg: EcgWidget) -> impl Widget<AppState> {
+    let bpm_label = Label::dynamic(|data: &AppState, _env: &Env| format!("{:.0} BPM", data.bpm))
+        .fix_width(80.0);
+    let bpm_slider = Slider::new()
+        .with_range(beat::MIN_BPM, beat::MAX_BPM)
+        .expand_width()
+        .lens(AppState::bpm);
+
+    let amplitude_label =
+        Label::dynamic(|data: &AppState, _env: &Env| format!("{:.0}%", data.amplitude * 100.0))
+            .fix_width(80.0);
+    let amplitude_slider = Slider::new()
+        .with_range(0.0, beat::MAX_AMPLITUDE)
+        .expand_width()
+        .lens(AppState::amplitude);
+
+    Flex::column()
+        .with_flex_child(heart.controller(ClockKeys), 1.0)
+        .with_child(ecg)
+        .with_child(
+            Flex::row()
+                .with_child(bpm_label)
+                .with_flex_child(bpm_slider, 1.0)
+                .padding((8.0, 4.0)),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(amplitude_label)
+                .with_flex_child(amplitude_slider, 1.0)
+                .padding((8.0, 4.0)),
+        )
+}
+
+fn main() -> ExitCode {
+    let matches = Cli::command().get_matches();
+    let cli = match Cli::from_arg_matches(&matches) {
+        Ok(cli) => cli,
+        Err(err) => err.exit(),
+    };
+    // Options given on the command line override the configuration file
+    let explicit: HashSet<String> = matches
+        .ids()
+        .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+        .map(|id| id.to_string())
+        .collect();
+
+    let config = match &cli.config {
+        Some(path) => match Config::load(path) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("error: failed to load `{}`: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Config::default(),
+    };
+
+    let args = config.heart_args(&cli.heart, |name| explicit.contains(name));
+    let builder = args.builder();
+    let painter = builder.painter();
+
+    let mut size = cli.size;
+    let mut title = cli.title.clone();
+    if !explicit.contains("size") {
+        size.width = config.window.width.unwrap_or(size.width);
+        size.height = config.window.height.unwrap_or(size.height);
+    }
+    if let (Some(config_title), false) = (&config.window.title, explicit.contains("title")) {
+        title = config_title.clone();
     }
 
-    /// Handles life cycle events for the HeartWidget.
-    ///
-    /// In particular, it handles the `WidgetAdded` event by requesting
-    /// an animation frame to start the animation loop.
-    ///
-    /// # Arguments
-    /// 
-    /// * `ctx` - The lifecycle context used to request animation frames.
-    /// * `event` - The lifecycle event being handled.
-    /// * `data` - The application state, which is currently unused.
-    /// * `_env` - The environment, which is currently unused.
-    fn lifecycle(
-        &mut self,
-        ctx: &mut LifeCycleCtx,
-        event: &LifeCycle,
-        _data: &AppState,
-        _env: &Env,
-    ) {
-        if let LifeCycle::WidgetAdded = event {
-            // Start the animation loop
-            ctx.request_anim_frame();
+    let osc_beats = match cli.osc_send.map(OscSender::connect).transpose() {
+        Ok(sender) => OscBeats::new(sender),
+        Err(err) => {
+            eprintln!("error: failed to open an OSC socket: {}", err);
+            return ExitCode::FAILURE;
         }
-    }
+    };
+
+    // Hearts given on the command line replace those listed in the file
+    let hearts: Vec<HeartEntry> = if cli.hearts.is_empty() {
+        config.dashboard_hearts(&args)
+    } else {
+        cli.hearts
+            .iter()
+            .map(|entry| entry.clone().amplitude(args.amplitude))
+            .collect()
+    };
+    if !hearts.is_empty() {
+        let single_heart_options = [
+            ("timeline", cli.timeline.is_some()),
+            ("listen", cli.listen.is_some()),
+            ("ble-mock", cli.ble_mock.is_some()),
+            ("osc-listen", cli.osc_listen.is_some()),
+        ];
+        if let Some((option, _)) = single_heart_options.iter().find(|(_, given)| *given) {
+            eprintln!(
+                "error: --{} drives a single heart and cannot be used with a dashboard",
+                option
+            );
+            return ExitCode::FAILURE;
+        }
+
+        let mut dashboard = DashboardWidget::new(painter, builder.themed());
+        let columns = cli.columns.map(|columns| columns as usize);
+        if let Some(columns) = columns.or(config.window.columns) {
+            dashboard = dashboard.columns(columns);
+        }
+        let main_window = WindowDesc::new(dashboard.controller(osc_beats))
+            .window_size(size)
+            .title(title);
 
-    fn update(&mut self, _ctx: &mut UpdateCtx, _old_data: &AppState, _data: &AppState, _env: &Env) {}
-
-    /// Computes the preferred size of the HeartWidget.
-    ///
-    /// The preferred size is the maximum size allowed by the `BoxConstraints`
-    /// passed in, which should be the size of the window. This makes the widget
-    /// fill the window.
-    ///
-    /// # Arguments
-    ///
-    /// * `_ctx` - The layout context, which is currently unused.
-    /// * `bc` - The box constraints that specify the maximum size allowed.
-    /// * `_data` - The application state, which is currently unused.
-    /// * `_env` - The environment, which is currently unused.
-    ///
-    /// # Returns
-    ///
-    /// The preferred size of the widget.
-    fn layout(
-        &mut self,
-        _ctx: &mut LayoutCtx,
-        bc: &BoxConstraints,
-        _data: &AppState,
-        _env: &Env,
-    ) -> Size {
-        bc.max() // Make the widget fill the window
+        let launcher = AppLauncher::with_window(main_window).log_to_console();
+        if let Some(path) = cli.config {
+            let sink = launcher.get_external_handle();
+            let keep = move |name: &str| explicit.contains(name);
+            if cli.hearts.is_empty() {
+                config::watch_app(path, cli.heart, keep, sink, config::reload_hearts);
+            } else {
+                config::watch_app(path, cli.heart, keep, sink, |_, _, _| {});
+            }
+        }
+        return launch(launcher, Arc::new(hearts));
     }
 
-/// Paints a heart shape on the widget with a beating animation effect.
-/// 
-/// The heart shape is drawn centered within the widget, and its size
-/// oscillates over time to simulate a beating effect. The heart is outlined
-/// in black and filled with red.
-/// 
-/// # Arguments
-/// 
-/// * `ctx` - The painting context used to draw the heart.
-/// * `data` - The application state, which provides the current time for the
-///            beating animation.
-/// * `_env` - The environment, which is currently unused.
-    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppState, _env: &Env) {
-        let size = ctx.size();
-        let center = Point::new(size.width / 2.0, size.height / 2.0);
-        let scale = 1.0 + 0.1 * f64::sin(data.time * 3.0); // Heart beating effect
-
-        // Define the heart shape
-        let mut path = BezPath::new();
-        let width = size.width.min(size.height) * 0.25 * scale;
-        let height = size.width.min(size.height) * 0.48 * scale;
-
-        // Start at the bottom tip of the heart
-        path.move_to(Point::new(center.x, center.y + height / 2.0));
-
-        // Left half of the heart
-        path.curve_to(
-            Point::new(center.x - width, center.y + height / 4.0),
-            Point::new(center.x - width, center.y - height / 2.0),
-            Point::new(center.x, center.y - height / 4.0),
-        );
-
-        // Right half of the heart
-        path.curve_to(
-            Point::new(center.x + width, center.y - height / 2.0),
-            Point::new(center.x + width, center.y + height / 4.0),
-            Point::new(center.x, center.y + height / 2.0),
-        );
-
-        path.close_path();
-
-        ctx.stroke(&path, &Color::rgb8(0, 0, 0), 4.0);
-
-        // Fill the heart with red color
-        ctx.fill(&path, &Color::rgb8(255, 0, 0));
+    let mut initial_state = builder.state();
+    if let Some(path) = &cli.timeline {
+        match Timeline::load(path) {
+            Ok(mut timeline) => {
+                timeline.align_to(&*painter.envelope);
+                initial_state.set_timeline(Some(Arc::new(timeline)));
+            }
+            Err(err) => {
+                eprintln!("error: failed to load `{}`: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        }
     }
-}
 
-fn main() {
-    let main_window = WindowDesc::new(HeartWidget)
-        .window_size((400.0, 400.0))
-        .title("Beating Heart");
+    let ecg = EcgWidget::new().align_to(&*painter.envelope);
+    let heart = builder.build();
+
+    #[cfg(feature = "audio")]
+    let heart = {
+        let player = if cli.mute {
+            None
+        } else {
+            AudioPlayer::start(&initial_state, &*painter.envelope)
+                .map_err(|err| eprintln!("warning: playing without sound: {}", err))
+                .ok()
+        };
+        heart.controller(HeartSounds(player))
+    };
+
+    let main_window = WindowDesc::new(build_ui(heart, ecg).controller(osc_beats))
+        .window_size(size)
+        .title(title);
+
+    let launcher = AppLauncher::with_window(main_window).log_to_console();
+    if let Some(endpoint) = &cli.listen {
+        let handler = live::app_handler(launcher.get_external_handle());
+        if let Err(err) = live::listen(endpoint, handler) {
+            eprintln!("error: failed to listen on {}: {}", endpoint, err);
+            return ExitCode::FAILURE;
+        }
+    }
+    if let Some(path) = &cli.ble_mock {
+        match MockTransport::open(path) {
+            Ok(transport) => {
+                ble::spawn(transport, live::app_handler(launcher.get_external_handle()));
+            }
+            Err(err) => {
+                eprintln!("error: failed to open `{}`: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Some(address) = cli.osc_listen {
+        let handler = osc::app_handler(launcher.get_external_handle());
+        if let Err(err) = osc::listen(address, handler) {
+            eprintln!("error: failed to listen for OSC on {}: {}", address, err);
+            return ExitCode::FAILURE;
+        }
+    }
+    if let Some(path) = cli.config {
+        let sink = launcher.get_external_handle();
+        let keep = move |name: &str| explicit.contains(name);
+        let on_reload = config::reload_heart(&args);
+        config::watch_app(path, cli.heart, keep, sink, on_reload);
+    }
 
-    let initial_state = AppState { time: 0.0 };
+    launch(launcher, initial_state)
+}
 
-    AppLauncher::with_window(main_window)
-        .log_to_console()
-        .launch(initial_state)
-        .expect("Failed to launch application");
+/// Runs the application until its last window closes.
+fn launch<T: Data>(launcher: AppLauncher<T>, data: T) -> ExitCode {
+    match launcher.launch(data) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: failed to launch application: {}", err);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,623 @@
+//! Open Sound Control input and output.
+//!
+//! Lighting desks, sound rigs and show controllers talk OSC over UDP. The
+//! application accepts these messages:
+//!
+//! | Address           | Arguments                           | Effect                     |
+//! |-------------------|-------------------------------------|----------------------------|
+//! | `/heart/bpm`      | a number                            | Sets the heart rate.       |
+//! | `/heart/amplitude`| a number                            | Sets the beat amplitude.   |
+//! | `/heart/color`    | `#rrggbb`, or three or four numbers | Sets the fill color.       |
+//!
+//! Color components given as floats range from 0.0 to 1.0, given as
+//! integers from 0 to 255. Messages inside bundles are applied at once; time
+//! tags are ignored.
+//!
+//! [`OscSender::send_beat`] announces every beat of the heart as a
+//! `/heart/beat` message carrying the beat's number and the heart rate, and
+//! the [`OscBeats`] controller does so for every heart it contains.
+
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+
+use druid::{widget::Controller, Color, Env, Event, EventCtx, ExtEventSink, Target, Widget};
+
+use crate::beat;
+use crate::color;
+use crate::events::{BeatEvent, BeatEventKind};
+use crate::widget::{BEAT_EVENT, SET_AMPLITUDE, SET_BPM, SET_FILL};
+
+/// Address setting the heart rate.
+pub const BPM_ADDRESS: &str = "/heart/bpm";
+/// Address setting the beat amplitude.
+pub const AMPLITUDE_ADDRESS: &str = "/heart/amplitude";
+/// Address setting the fill color.
+pub const COLOR_ADDRESS: &str = "/heart/color";
+/// Address of the message sent on every beat.
+pub const BEAT_ADDRESS: &str = "/heart/beat";
+
+/// Largest packet accepted, in bytes.
+const MAX_PACKET: usize = 8192;
+
+/// The header opening a bundle.
+const BUNDLE_TAG: &[u8] = b"#bundle\0";
+
+/// An error raised while decoding an OSC packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OscError {
+    /// The packet ends inside a field.
+    Truncated(&'static str),
+    /// A string is not valid UTF-8.
+    BadString,
+    /// The address does not start with `/`.
+    BadAddress(String),
+    /// An argument has a type this decoder does not know.
+    UnknownType(char),
+}
+
+impl fmt::Display for OscError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OscError::Truncated(field) => write!(f, "the packet ends inside the {}", field),
+            OscError::BadString => f.write_str("the packet holds a string that is not UTF-8"),
+            OscError::BadAddress(address) => write!(f, "`{}` is not an OSC address", address),
+            OscError::UnknownType(tag) => write!(f, "unknown argument type `{}`", tag),
+        }
+    }
+}
+
+impl std::error::Error for OscError {}
+
+/// An argument of an OSC message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscArg {
+    /// A 32-bit integer, type tag `i`.
+    Int(i32),
+    /// A 64-bit integer, type tag `h`.
+    Long(i64),
+    /// A 32-bit float, type tag `f`.
+    Float(f32),
+    /// A 64-bit float, type tag `d`.
+    Double(f64),
+    /// A string, type tag `s`.
+    String(String),
+    /// Raw bytes, type tag `b`.
+    Blob(Vec<u8>),
+    /// A boolean, type tags `T` and `F`.
+    Bool(bool),
+    /// No value, type tag `N`.
+    Nil,
+}
+
+impl OscArg {
+    /// Returns the argument as a number, if it is one.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            OscArg::Int(value) => Some(value as f64),
+            OscArg::Long(value) => Some(value as f64),
+            OscArg::Float(value) => Some(value as f64),
+            OscArg::Double(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// A single OSC message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OscMessage {
+    /// Where the message is addressed, such as `/heart/bpm`.
+    pub address: String,
+    /// The message's arguments.
+    pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+    /// Creates a message without arguments.
+    pub fn new(address: impl Into<String>) -> Self {
+        OscMessage {
+            address: address.into(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Appends an argument.
+    pub fn arg(mut self, arg: OscArg) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    /// Encodes the message as a packet.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut packet = Vec::new();
+        write_string(&mut packet, &self.address);
+        let tags: String = std::iter::once(',')
+            .chain(self.args.iter().map(|arg| match arg {
+                OscArg::Int(_) => 'i',
+                OscArg::Long(_) => 'h',
+                OscArg::Float(_) => 'f',
+                OscArg::Double(_) => 'd',
+                OscArg::String(_) => 's',
+                OscArg::Blob(_) => 'b',
+                OscArg::Bool(true) => 'T',
+                OscArg::Bool(false) => 'F',
+                OscArg::Nil => 'N',
+            }))
+            .collect();
+        write_string(&mut packet, &tags);
+        for arg in &self.args {
+            match arg {
+                OscArg::Int(value) => packet.extend(value.to_be_bytes()),
+                OscArg::Long(value) => packet.extend(value.to_be_bytes()),
+                OscArg::Float(value) => packet.extend(value.to_be_bytes()),
+                OscArg::Double(value) => packet.extend(value.to_be_bytes()),
+                OscArg::String(value) => write_string(&mut packet, value),
+                OscArg::Blob(bytes) => {
+                    packet.extend((bytes.len() as i32).to_be_bytes());
+                    packet.extend(bytes);
+                    pad(&mut packet);
+                }
+                OscArg::Bool(_) | OscArg::Nil => {}
+            }
+        }
+        packet
+    }
+
+    /// Decodes a packet, flattening bundles into the messages they contain.
+    pub fn decode_packet(packet: &[u8]) -> Result<Vec<OscMessage>, OscError> {
+        let mut messages = Vec::new();
+        decode_into(packet, &mut messages)?;
+        Ok(messages)
+    }
+
+    /// Decodes a packet holding a single message.
+    fn decode(mut packet: &[u8]) -> Result<OscMessage, OscError> {
+        let address = read_string(&mut packet, "address")?;
+        if !address.starts_with('/') {
+            return Err(OscError::BadAddress(address));
+        }
+        // Very old senders omit the type tags of messages without arguments
+        if packet.is_empty() {
+            return Ok(OscMessage::new(address));
+        }
+        let tags = read_string(&mut packet, "type tags")?;
+        let mut args = Vec::new();
+        for tag in tags.strip_prefix(',').unwrap_or(&tags).chars() {
+            let arg = match tag {
+                'i' => OscArg::Int(i32::from_be_bytes(read_array(&mut packet, "integer")?)),
+                'h' => OscArg::Long(i64::from_be_bytes(read_array(&mut packet, "integer")?)),
+                'f' => OscArg::Float(f32::from_be_bytes(read_array(&mut packet, "float")?)),
+                'd' => OscArg::Double(f64::from_be_bytes(read_array(&mut packet, "float")?)),
+                's' | 'S' => OscArg::String(read_string(&mut packet, "string")?),
+                'b' => OscArg::Blob(read_blob(&mut packet)?),
+                'T' => OscArg::Bool(true),
+                'F' => OscArg::Bool(false),
+                'N' => OscArg::Nil,
+                tag => return Err(OscError::UnknownType(tag)),
+            };
+            args.push(arg);
+        }
+        Ok(OscMessage { address, args })
+    }
+}
+
+/// A change requested over OSC.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscCommand {
+    /// Set the heart rate in beats per minute.
+    Bpm(f64),
+    /// Set the relative size change at the peak of a beat.
+    Amplitude(f64),
+    /// Set the color the heart is filled with.
+    Color(Color),
+}
+
+impl TryFrom<&OscMessage> for OscCommand {
+    type Error = String;
+
+    fn try_from(message: &OscMessage) -> Result<Self, Self::Error> {
+        let number = || match message.args.as_slice() {
+            [arg] => arg
+                .as_f64()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| format!("{} expects a number, not {:?}", message.address, arg)),
+            args => Err(format!(
+                "{} expects one number, not {} arguments",
+                message.address,
+                args.len()
+            )),
+        };
+        match message.address.as_str() {
+            BPM_ADDRESS => Ok(OscCommand::Bpm(beat::clamp_bpm(number()?))),
+            AMPLITUDE_ADDRESS => Ok(OscCommand::Amplitude(beat::clamp_amplitude(number()?))),
+            COLOR_ADDRESS => color_from_args(&message.args)
+                .map(OscCommand::Color)
+                .ok_or_else(|| {
+                    format!(
+                        "{} expects `#rrggbb` or three or four numbers, not {:?}",
+                        message.address, message.args
+                    )
+                }),
+            address => Err(format!("unknown address {}", address)),
+        }
+    }
+}
+
+/// Receives OSC commands on a UDP socket.
+///
+/// The socket is bound before returning, so errors such as an address
+/// already in use are reported right away. Commands are then delivered on a
+/// background thread; packets and messages that cannot be understood are
+/// passed on as errors. `on_command` returns `false` to stop.
+///
+/// # Arguments
+///
+/// * `address` - The address to listen on, such as `0.0.0.0:9000`.
+/// * `on_command` - Called with every received message.
+pub fn listen<F>(address: SocketAddr, mut on_command: F) -> io::Result<thread::JoinHandle<()>>
+where
+    F: FnMut(Result<OscCommand, String>) -> bool + Send + 'static,
+{
+    let socket = UdpSocket::bind(address)?;
+    Ok(thread::spawn(move || {
+        let mut buffer = [0; MAX_PACKET];
+        while let Ok(length) = socket.recv(&mut buffer) {
+            let results: Vec<Result<OscCommand, String>> =
+                match OscMessage::decode_packet(&buffer[..length]) {
+                    Ok(messages) => messages.iter().map(OscCommand::try_from).collect(),
+                    Err(err) => vec![Err(format!("bad OSC packet: {}", err))],
+                };
+            for result in results {
+                if !on_command(result) {
+                    return;
+                }
+            }
+        }
+    }))
+}
+
+/// Sends OSC messages to a UDP address.
+pub struct OscSender {
+    socket: UdpSocket,
+    target: SocketAddr,
+}
+
+impl OscSender {
+    /// Opens a socket sending to `target`.
+    pub fn connect(target: SocketAddr) -> io::Result<OscSender> {
+        let local: SocketAddr = if target.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        // Receivers of show control often sit on the broadcast address
+        socket.set_broadcast(true)?;
+        Ok(OscSender { socket, target })
+    }
+
+    /// Sends one message.
+    pub fn send(&self, message: &OscMessage) -> io::Result<()> {
+        self.socket.send_to(&message.encode(), self.target)?;
+        Ok(())
+    }
+
+    /// Announces a beat event.
+    ///
+    /// The heart's strongest contraction is sent as a `/heart/beat` message
+    /// with the beat's number and the heart rate, so receivers flash on the
+    /// same frame the heart does; other stages of the beat are skipped.
+    pub fn send_beat(&self, event: &BeatEvent) -> io::Result<()> {
+        if event.kind != BeatEventKind::Systole {
+            return Ok(());
+        }
+        let message = OscMessage::new(BEAT_ADDRESS)
+            .arg(OscArg::Int(event.beat as i32))
+            .arg(OscArg::Float(event.bpm as f32));
+        self.send(&message)
+    }
+}
+
+/// Returns a handler for [`listen`] passing OSC commands on to a running
+/// application whose data is an [`AppState`]. Rates, amplitudes and colors
+/// are sent as [`SET_BPM`], [`SET_AMPLITUDE`] and [`SET_FILL`] commands and
+/// errors are reported on standard error. The handler returns `false` once
+/// the application has quit.
+///
+/// [`AppState`]: crate::AppState
+pub fn app_handler(sink: ExtEventSink) -> impl FnMut(Result<OscCommand, String>) -> bool {
+    move |command| {
+        let sent = match command {
+            Ok(OscCommand::Bpm(bpm)) => sink.submit_command(SET_BPM, bpm, Target::Auto),
+            Ok(OscCommand::Amplitude(amplitude)) => {
+                sink.submit_command(SET_AMPLITUDE, amplitude, Target::Auto)
+            }
+            Ok(OscCommand::Color(color)) => sink.submit_command(SET_FILL, color, Target::Auto),
+            Err(err) => {
+                eprintln!("warning: ignoring OSC input: {}", err);
+                Ok(())
+            }
+        };
+        // Stop listening once the application has quit
+        sent.is_ok()
+    }
+}
+
+/// Announces the beats of the hearts it contains over OSC.
+///
+/// Failures to send are reported on standard error once.
+pub struct OscBeats {
+    /// Where beats are sent; `None` when no address to send to was given.
+    sender: Option<OscSender>,
+    /// Whether a failure to send was reported already.
+    failed: bool,
+}
+
+impl OscBeats {
+    /// Creates a controller sending beats with `sender`, if any.
+    pub fn new(sender: Option<OscSender>) -> Self {
+        OscBeats {
+            sender,
+            failed: false,
+        }
+    }
+}
+
+impl<T, W: Widget<T>> Controller<T, W> for OscBeats {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        if let (Event::Notification(notification), Some(sender)) = (event, &self.sender) {
+            if let Some(beat_event) = notification.get(BEAT_EVENT) {
+                if let (Err(err), false) = (sender.send_beat(beat_event), self.failed) {
+                    eprintln!("warning: failed to send OSC beat: {}", err);
+                    self.failed = true;
+                }
+            }
+        }
+        child.event(ctx, event, data, env);
+    }
+}
+
+/// Decodes a packet, which is a message or a bundle, into `messages`.
+fn decode_into(packet: &[u8], messages: &mut Vec<OscMessage>) -> Result<(), OscError> {
+    let Some(mut elements) = packet.strip_prefix(BUNDLE_TAG) else {
+        messages.push(OscMessage::decode(packet)?);
+        return Ok(());
+    };
+    // The time tag is ignored: bundled messages take effect at once
+    read_array::<8>(&mut elements, "time tag")?;
+    while !elements.is_empty() {
+        let element = read_blob(&mut elements)?;
+        decode_into(&element, messages)?;
+    }
+    Ok(())
+}
+
+/// Reads a color from the arguments of a color message.
+fn color_from_args(args: &[OscArg]) -> Option<Color> {
+    if let [OscArg::String(hex)] = args {
+        return color::parse_color(hex).ok();
+    }
+    let component = |arg: &OscArg| -> Option<u8> {
+        let value = match arg {
+            OscArg::Float(_) | OscArg::Double(_) => arg.as_f64()? * 255.0,
+            _ => arg.as_f64()?,
+        };
+        value
+            .is_finite()
+            .then(|| value.round().clamp(0.0, 255.0) as u8)
+    };
+    let components: Option<Vec<u8>> = args.iter().map(component).collect();
+    match *components?.as_slice() {
+        [r, g, b] => Some(Color::rgb8(r, g, b)),
+        [r, g, b, a] => Some(Color::rgba8(r, g, b, a)),
+        _ => None,
+    }
+}
+
+/// Appends a string, terminated and padded to a multiple of four bytes.
+fn write_string(packet: &mut Vec<u8>, text: &str) {
+    packet.extend(text.as_bytes());
+    packet.push(0);
+    pad(packet);
+}
+
+/// Pads with zero bytes to a multiple of four bytes.
+fn pad(packet: &mut Vec<u8>) {
+    while !packet.len().is_multiple_of(4) {
+        packet.push(0);
+    }
+}
+
+/// Reads a padded string from the front of a packet.
+fn read_string(packet: &mut &[u8], field: &'static str) -> Result<String, OscError> {
+    let end = packet
+        .iter()
+        .position(|&byte| byte == 0)
+        .ok_or(OscError::Truncated(field))?;
+    let text = std::str::from_utf8(&packet[..end]).map_err(|_| OscError::BadString)?;
+    let text = text.to_string();
+    skip(packet, (end + 4) & !3, field)?;
+    Ok(text)
+}
+
+/// Reads a size-prefixed, padded byte string from the front of a packet.
+fn read_blob(packet: &mut &[u8]) -> Result<Vec<u8>, OscError> {
+    let size = i32::from_be_bytes(read_array(packet, "blob")?);
+    let size = usize::try_from(size).map_err(|_| OscError::Truncated("blob"))?;
+    let bytes = packet
+        .get(..size)
+        .ok_or(OscError::Truncated("blob"))?
+        .to_vec();
+    skip(packet, (size + 3) & !3, "blob")?;
+    Ok(bytes)
+}
+
+/// Reads a fixed number of bytes from the front of a packet.
+fn read_array<const N: usize>(
+    packet: &mut &[u8],
+    field: &'static str,
+) -> Result<[u8; N], OscError> {
+    let (value, rest) = packet
+        .split_first_chunk::<N>()
+        .ok_or(OscError::Truncated(field))?;
+    *packet = rest;
+    Ok(*value)
+}
+
+/// Drops `count` bytes from the front of a packet.
+fn skip(packet: &mut &[u8], count: usize, field: &'static str) -> Result<(), OscError> {
+    *packet = packet.get(count..).ok_or(OscError::Truncated(field))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a bundle holding the given packets, with an immediate time
+    /// tag.
+    fn bundle(elements: &[Vec<u8>]) -> Vec<u8> {
+        let mut packet = BUNDLE_TAG.to_vec();
+        packet.extend([0, 0, 0, 0, 0, 0, 0, 1]);
+        for element in elements {
+            packet.extend((element.len() as i32).to_be_bytes());
+            packet.extend(element);
+        }
+        packet
+    }
+
+    #[test]
+    fn encode_pads_to_four_bytes() {
+        assert_eq!(OscMessage::new("/a").encode(), b"/a\0\0,\0\0\0");
+        assert_eq!(OscMessage::new("/abc").encode(), b"/abc\0\0\0\0,\0\0\0");
+
+        let message = OscMessage::new("/heart/bpm")
+            .arg(OscArg::String("abc".to_string()))
+            .arg(OscArg::Blob(vec![1, 2, 3, 4, 5]));
+        let packet = message.encode();
+        assert_eq!(packet.len() % 4, 0);
+        assert_eq!(
+            &packet[16..],
+            b"abc\0\0\0\0\x05\x01\x02\x03\x04\x05\0\0\0".as_slice()
+        );
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        let message = OscMessage::new("/heart/test")
+            .arg(OscArg::Int(-7))
+            .arg(OscArg::Long(1 << 40))
+            .arg(OscArg::Float(0.5))
+            .arg(OscArg::Double(72.25))
+            .arg(OscArg::String("beat".to_string()))
+            .arg(OscArg::Blob(vec![0xde, 0xad, 0xbe]))
+            .arg(OscArg::Bool(true))
+            .arg(OscArg::Bool(false))
+            .arg(OscArg::Nil);
+        assert_eq!(
+            OscMessage::decode_packet(&message.encode()),
+            Ok(vec![message])
+        );
+    }
+
+    #[test]
+    fn decodes_nested_bundles() {
+        let bpm = OscMessage::new(BPM_ADDRESS).arg(OscArg::Float(90.0));
+        let amplitude = OscMessage::new(AMPLITUDE_ADDRESS).arg(OscArg::Double(0.2));
+        let color = OscMessage::new(COLOR_ADDRESS).arg(OscArg::String("#ff0000".to_string()));
+        let packet = bundle(&[bpm.encode(), bundle(&[amplitude.encode(), color.encode()])]);
+        assert_eq!(
+            OscMessage::decode_packet(&packet),
+            Ok(vec![bpm, amplitude, color])
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_packets() {
+        let message = OscMessage::new("/heart/test")
+            .arg(OscArg::Int(1))
+            .arg(OscArg::Blob(vec![1, 2, 3]));
+        let packet = message.encode();
+        // Every prefix fails without panicking, except the bare address of
+        // a message from a sender omitting type tags
+        for len in 0..packet.len() {
+            let result = OscMessage::decode_packet(&packet[..len]);
+            assert_eq!(result.is_ok(), len == 12, "{} bytes", len);
+        }
+
+        assert_eq!(
+            OscMessage::decode_packet(b"/a\0\0,i\0\0\0\0"),
+            Err(OscError::Truncated("integer"))
+        );
+        assert_eq!(
+            OscMessage::decode_packet(b"/a\0\0,b\0\0\0\0\0\x08abcd"),
+            Err(OscError::Truncated("blob"))
+        );
+        assert_eq!(
+            OscMessage::decode_packet(b"/a\0\0,b\0\0\xff\xff\xff\xff"),
+            Err(OscError::Truncated("blob"))
+        );
+        let mut packet = bundle(&[OscMessage::new("/a").encode()]);
+        packet.truncate(packet.len() - 2);
+        assert_eq!(
+            OscMessage::decode_packet(&packet),
+            Err(OscError::Truncated("blob"))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_messages() {
+        assert_eq!(
+            OscMessage::decode_packet(b"heart\0\0\0,\0\0\0"),
+            Err(OscError::BadAddress("heart".to_string()))
+        );
+        assert_eq!(
+            OscMessage::decode_packet(b"/a\0\0,x\0\0"),
+            Err(OscError::UnknownType('x'))
+        );
+        assert_eq!(
+            OscMessage::decode_packet(b"/a\xff\0,\0\0\0"),
+            Err(OscError::BadString)
+        );
+    }
+
+    #[test]
+    fn parses_colors() {
+        let string = |text: &str| color_from_args(&[OscArg::String(text.to_string())]);
+        assert_eq!(string("#ff8000"), Some(Color::rgb8(255, 128, 0)));
+        assert_eq!(string("orange"), None);
+
+        let ints = [OscArg::Int(255), OscArg::Int(128), OscArg::Int(300)];
+        assert_eq!(color_from_args(&ints), Some(Color::rgb8(255, 128, 255)));
+        let floats = [
+            OscArg::Float(1.0),
+            OscArg::Float(0.5),
+            OscArg::Double(0.0),
+            OscArg::Double(0.25),
+        ];
+        assert_eq!(
+            color_from_args(&floats),
+            Some(Color::rgba8(255, 128, 0, 64))
+        );
+
+        assert_eq!(color_from_args(&[OscArg::Int(1), OscArg::Int(2)]), None);
+        let mixed = [OscArg::Int(1), OscArg::Nil, OscArg::Int(2)];
+        assert_eq!(color_from_args(&mixed), None);
+    }
+
+    #[test]
+    fn commands_from_messages() {
+        let command = |message: OscMessage| OscCommand::try_from(&message);
+        assert_eq!(
+            command(OscMessage::new(BPM_ADDRESS).arg(OscArg::Int(1000))),
+            Ok(OscCommand::Bpm(beat::MAX_BPM))
+        );
+        assert_eq!(
+            command(OscMessage::new(COLOR_ADDRESS).arg(OscArg::String("#00ff00".to_string()))),
+            Ok(OscCommand::Color(Color::rgb8(0, 255, 0)))
+        );
+        assert!(command(OscMessage::new(BPM_ADDRESS)).is_err());
+        assert!(command(OscMessage::new("/heart/unknown")).is_err());
+    }
+}
@@ -0,0 +1,382 @@
+//! Painting the heart onto any piet render context.
+//!
+//! [`HeartPainter`] holds everything that decides what a frame looks like,
+//! so the widget and the headless renderer draw exactly the same heart.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use druid::{
+    kurbo::{BezPath, Point, Rect, Shape},
+    piet::{Color, LineCap, LineJoin, RenderContext, StrokeStyle},
+    Size,
+};
+
+use crate::color::ColorRamp;
+use crate::envelope::{BeatEnvelope, EnvelopeProfile};
+use crate::shapes::Icon;
+use crate::state::AppState;
+use crate::style::HeartStyle;
+
+/// Fill color used unless configured otherwise.
+pub const DEFAULT_FILL: Color = Color::rgb8(255, 0, 0);
+/// Outline color used unless configured otherwise.
+pub const DEFAULT_STROKE: Color = Color::rgb8(0, 0, 0);
+/// Outline width used unless configured otherwise.
+pub const DEFAULT_STROKE_WIDTH: f64 = 4.0;
+
+/// Width of the resting heart as a fraction of the canvas' shorter side.
+const HEART_WIDTH: f64 = 0.375;
+/// Height of the resting heart as a fraction of the canvas' shorter side.
+const HEART_HEIGHT: f64 = 0.384;
+
+/// How far beyond the heart an outside outline may reach, in outline
+/// widths; enough for mitered corners up to piet's default limit.
+const OUTSIDE_REACH: f64 = 20.0;
+
+/// The order in which the heart's fill and outline are painted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PaintOrder {
+    /// The fill first, so the whole outline shows on top of it.
+    #[default]
+    FillFirst,
+    /// The outline first, so the fill covers the part inside the edge.
+    StrokeFirst,
+}
+
+impl PaintOrder {
+    /// Every paint order, in the order they are presented to users.
+    pub const ALL: [PaintOrder; 2] = [PaintOrder::FillFirst, PaintOrder::StrokeFirst];
+
+    /// Returns the name used to select the order in options and files.
+    pub fn name(self) -> &'static str {
+        match self {
+            PaintOrder::FillFirst => "fill-first",
+            PaintOrder::StrokeFirst => "stroke-first",
+        }
+    }
+}
+
+impl fmt::Display for PaintOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PaintOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PaintOrder::ALL
+            .into_iter()
+            .find(|order| order.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = PaintOrder::ALL.iter().map(|o| o.name()).collect();
+                format!(
+                    "unknown paint order `{}` (expected one of: {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Where the heart's outline lies relative to its edge.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StrokeAlign {
+    /// Centered on the edge, half inside and half outside the heart.
+    #[default]
+    Centered,
+    /// Entirely inside the heart, which keeps its overall size.
+    Inside,
+    /// Entirely outside the heart, which keeps the whole fill visible.
+    Outside,
+}
+
+impl StrokeAlign {
+    /// Every alignment, in the order they are presented to users.
+    pub const ALL: [StrokeAlign; 3] = [
+        StrokeAlign::Centered,
+        StrokeAlign::Inside,
+        StrokeAlign::Outside,
+    ];
+
+    /// Returns the name used to select the alignment in options and files.
+    pub fn name(self) -> &'static str {
+        match self {
+            StrokeAlign::Centered => "centered",
+            StrokeAlign::Inside => "inside",
+            StrokeAlign::Outside => "outside",
+        }
+    }
+}
+
+impl fmt::Display for StrokeAlign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for StrokeAlign {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StrokeAlign::ALL
+            .into_iter()
+            .find(|align| align.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = StrokeAlign::ALL.iter().map(|a| a.name()).collect();
+                format!(
+                    "unknown stroke alignment `{}` (expected one of: {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// How the heart's outline follows its edge.
+///
+/// Unlike piet's [`StrokeStyle`], the options can be sent to other threads,
+/// e.g. along with a reloaded painter.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StrokeOptions {
+    /// Where the outline lies relative to the edge.
+    pub align: StrokeAlign,
+    /// How the outline turns corners.
+    pub join: LineJoin,
+    /// How the ends of dashes are drawn.
+    pub cap: LineCap,
+    /// Alternating lengths of dashes and gaps, in pixels; empty for a solid
+    /// outline.
+    pub dash: Vec<f64>,
+    /// Distance into the dash pattern at which the outline starts.
+    pub dash_offset: f64,
+}
+
+impl StrokeOptions {
+    /// Returns the piet stroke style drawing these options.
+    pub fn stroke_style(&self) -> StrokeStyle {
+        let mut style = StrokeStyle::new()
+            .line_join(self.join)
+            .line_cap(self.cap)
+            .dash_offset(self.dash_offset);
+        if !self.dash.is_empty() {
+            style.set_dash_pattern(self.dash.clone());
+        }
+        style
+    }
+}
+
+/// Describes how to draw a heart at any point of its beat.
+#[derive(Clone)]
+pub struct HeartPainter {
+    /// The color the canvas is cleared with; `None` leaves it untouched.
+    pub background: Option<Color>,
+    /// The color the heart is filled with.
+    pub fill: Color,
+    /// The colors the fill passes through over a beat; `None` keeps it
+    /// steady.
+    pub color_ramp: Option<ColorRamp>,
+    /// The color of the heart's outline.
+    pub stroke: Color,
+    /// The width of the heart's outline; zero disables it.
+    pub stroke_width: f64,
+    /// How the outline follows the heart's edge.
+    pub stroke_options: StrokeOptions,
+    /// The order in which the fill and the outline are painted.
+    pub paint_order: PaintOrder,
+    /// The outline the heart pulses with.
+    pub shape: Icon,
+    /// Shapes the size change over the course of each beat.
+    pub envelope: Arc<dyn BeatEnvelope>,
+    /// The gradient, shadow, glow and highlight drawn with the heart.
+    pub style: HeartStyle,
+}
+
+impl HeartPainter {
+    /// Returns the heart's size relative to its resting size.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The animation model, which provides the beat phase and
+    ///   amplitude.
+    pub fn scale(&self, state: &AppState) -> f64 {
+        1.0 + state.amplitude * self.envelope.value(state.phase())
+    }
+
+    /// Returns how far the heart is contracted, from 0.0 at rest to 1.0 at
+    /// the peak of a beat, regardless of the amplitude.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The animation model, which provides the beat phase.
+    pub fn pulse(&self, state: &AppState) -> f64 {
+        self.envelope.value(state.phase()).clamp(0.0, 1.0)
+    }
+
+    /// Returns the fill color at a contraction of `pulse`, as returned by
+    /// [`pulse`](Self::pulse).
+    pub fn fill_at(&self, pulse: f64) -> Color {
+        match &self.color_ramp {
+            Some(ramp) => ramp.color(self.fill, pulse),
+            None => self.fill,
+        }
+    }
+
+    /// Returns the heart's outline as painted on a canvas of the given size.
+    ///
+    /// The heart is centered on the canvas and scaled by the beat.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The size of the canvas.
+    /// * `state` - The animation model, which provides the beat phase and
+    ///   amplitude.
+    pub fn path(&self, size: Size, state: &AppState) -> BezPath {
+        self.path_at_scale(size, self.scale(state))
+    }
+
+    /// Returns the heart's outline on a canvas of the given size, scaled by
+    /// `scale` relative to its resting size.
+    pub(crate) fn path_at_scale(&self, size: Size, scale: f64) -> BezPath {
+        let center = Point::new(size.width / 2.0, size.height / 2.0);
+        let side = size.width.min(size.height) * scale;
+        let rect = Rect::from_center_size(center, (side * HEART_WIDTH, side * HEART_HEIGHT));
+        self.shape.path(rect)
+    }
+
+    /// Returns whether a point lies inside the heart as painted on a canvas
+    /// of the given size.
+    ///
+    /// The test follows the heart's curved outline, so the corners of its
+    /// bounding box and the cleft between the lobes are outside.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The size of the canvas.
+    /// * `state` - The animation model, which provides the beat phase and
+    ///   amplitude.
+    /// * `point` - The point to test, in canvas coordinates.
+    pub fn hit_test(&self, size: Size, state: &AppState, point: Point) -> bool {
+        self.path(size, state).contains(point)
+    }
+
+    /// Paints the background, if any, and the heart onto a render context.
+    ///
+    /// # Arguments
+    ///
+    /// * `rc` - The render context to paint into.
+    /// * `size` - The size of the canvas.
+    /// * `state` - The animation model, which provides the beat phase and
+    ///   amplitude.
+    pub fn paint(&self, rc: &mut impl RenderContext, size: Size, state: &AppState) {
+        self.paint_path(rc, size, &self.path(size, state), self.pulse(state));
+    }
+
+    /// Paints the background, if any, and a heart with the given outline,
+    /// styled for a contraction of `pulse` as returned by
+    /// [`pulse`](Self::pulse).
+    pub(crate) fn paint_path(
+        &self,
+        rc: &mut impl RenderContext,
+        size: Size,
+        path: &BezPath,
+        pulse: f64,
+    ) {
+        if let Some(background) = self.background {
+            rc.fill(size.to_rect(), &background);
+        }
+
+        let fill = self.fill_at(pulse);
+        self.style.paint_under(rc, path, fill, pulse);
+
+        match self.paint_order {
+            PaintOrder::FillFirst => {
+                self.paint_fill(rc, path, fill, pulse);
+                self.paint_stroke(rc, path);
+            }
+            PaintOrder::StrokeFirst => {
+                self.paint_stroke(rc, path);
+                self.paint_fill(rc, path, fill, pulse);
+            }
+        }
+    }
+
+    /// Fills the heart and adds its highlight, unless the shape is drawn as
+    /// an outline only.
+    fn paint_fill(&self, rc: &mut impl RenderContext, path: &BezPath, fill: Color, pulse: f64) {
+        if self.shape.is_filled() {
+            self.style.paint_fill(rc, path, fill, pulse);
+            self.style.paint_over(rc, path, pulse);
+        }
+    }
+
+    /// Paints the heart's outline, aligned to its edge as configured.
+    fn paint_stroke(&self, rc: &mut impl RenderContext, path: &BezPath) {
+        if self.stroke_width <= 0.0 {
+            return;
+        }
+
+        let style = self.stroke_options.stroke_style();
+        let clip = match self.stroke_options.align {
+            StrokeAlign::Centered => {
+                rc.stroke_styled(path, &self.stroke, self.stroke_width, &style);
+                return;
+            }
+            StrokeAlign::Inside => path.clone(),
+            StrokeAlign::Outside => outside(path, self.stroke_width * OUTSIDE_REACH),
+        };
+
+        // A doubled outline clipped to one side of the edge
+        let _ = rc.with_save(|rc| {
+            rc.clip(&clip);
+            rc.stroke_styled(path, &self.stroke, 2.0 * self.stroke_width, &style);
+            Ok(())
+        });
+    }
+}
+
+impl Default for HeartPainter {
+    fn default() -> Self {
+        HeartPainter {
+            background: None,
+            fill: DEFAULT_FILL,
+            color_ramp: None,
+            stroke: DEFAULT_STROKE,
+            stroke_width: DEFAULT_STROKE_WIDTH,
+            stroke_options: StrokeOptions::default(),
+            paint_order: PaintOrder::default(),
+            shape: Icon::default(),
+            envelope: Arc::new(EnvelopeProfile::default()),
+            style: HeartStyle::default(),
+        }
+    }
+}
+
+/// Returns a shape covering everything within `reach` of a path except the
+/// inside of the path itself.
+fn outside(path: &BezPath, reach: f64) -> BezPath {
+    let bounds = path.bounding_box().inflate(reach, reach);
+    let mut corners = [
+        Point::new(bounds.x0, bounds.y0),
+        Point::new(bounds.x1, bounds.y0),
+        Point::new(bounds.x1, bounds.y1),
+        Point::new(bounds.x0, bounds.y1),
+    ];
+
+    // Wind the frame against the path, so the path cuts a hole into it
+    if bounds.area().signum() == path.area().signum() {
+        corners.reverse();
+    }
+
+    let mut frame = BezPath::new();
+    frame.move_to(corners[0]);
+    for corner in &corners[1..] {
+        frame.line_to(*corner);
+    }
+    frame.close_path();
+    frame.extend(path.iter());
+    frame
+}
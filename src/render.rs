@@ -0,0 +1,168 @@
+//! Headless rendering of heart frames.
+//!
+//! Frames are painted by the same [`HeartPainter`] the widget uses, but into
+//! an offscreen bitmap instead of a window, so no display is needed.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use druid::{piet::RenderContext, Size};
+use piet_common::{Device, ImageFormat};
+
+use crate::paint::HeartPainter;
+use crate::state::AppState;
+
+/// An error raised while rendering or saving a frame.
+#[derive(Debug)]
+pub enum RenderError {
+    /// The drawing backend failed.
+    Backend(piet_common::Error),
+    /// The requested image has no pixels.
+    EmptyImage,
+    /// Encoding the image failed.
+    Encoding(String),
+    /// Writing the image failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Backend(err) => write!(f, "rendering failed: {}", err),
+            RenderError::EmptyImage => f.write_str("image width and height must be non-zero"),
+            RenderError::Encoding(err) => write!(f, "encoding failed: {}", err),
+            RenderError::Io(err) => write!(f, "writing failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<piet_common::Error> for RenderError {
+    fn from(err: piet_common::Error) -> Self {
+        RenderError::Backend(err)
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(err: io::Error) -> Self {
+        RenderError::Io(err)
+    }
+}
+
+impl From<png::EncodingError> for RenderError {
+    fn from(err: png::EncodingError) -> Self {
+        RenderError::Encoding(err.to_string())
+    }
+}
+
+impl From<gif::EncodingError> for RenderError {
+    fn from(err: gif::EncodingError) -> Self {
+        RenderError::Encoding(err.to_string())
+    }
+}
+
+/// A rendered frame with straight (not premultiplied) alpha.
+#[derive(Clone, Debug)]
+pub struct RgbaImage {
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+    /// Pixels in row-major order, four bytes (red, green, blue, alpha) each.
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Writes the image to a PNG file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to create or overwrite.
+    pub fn write_png(&self, path: impl AsRef<Path>) -> Result<(), RenderError> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+}
+
+/// Renders a single frame of the heart into memory.
+///
+/// # Arguments
+///
+/// * `painter` - Describes how the heart is drawn.
+/// * `state` - The animation model at the moment to render.
+/// * `width` - The width of the frame in pixels.
+/// * `height` - The height of the frame in pixels.
+///
+/// The frame is transparent wherever the painter draws neither background
+/// nor heart.
+pub fn render_frame(
+    painter: &HeartPainter,
+    state: &AppState,
+    width: u32,
+    height: u32,
+) -> Result<RgbaImage, RenderError> {
+    if width == 0 || height == 0 {
+        return Err(RenderError::EmptyImage);
+    }
+
+    let mut device = Device::new()?;
+    let mut target = device.bitmap_target(width as usize, height as usize, 1.0)?;
+    {
+        let mut rc = target.render_context();
+        painter.paint(&mut rc, Size::new(width as f64, height as f64), state);
+        rc.finish()?;
+    }
+
+    let mut pixels = vec![0; width as usize * height as usize * 4];
+    target.copy_raw_pixels(ImageFormat::RgbaPremul, &mut pixels)?;
+    unpremultiply(&mut pixels);
+
+    Ok(RgbaImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Renders the heart as it looks `time` seconds into the animation and
+/// writes it to a PNG file.
+///
+/// # Arguments
+///
+/// * `painter` - Describes how the heart is drawn.
+/// * `state` - The animation model; its heart rate and amplitude are used.
+/// * `time` - The animation time to render, in seconds.
+/// * `width` - The width of the image in pixels.
+/// * `height` - The height of the image in pixels.
+/// * `path` - The file to create or overwrite.
+pub fn render_png(
+    painter: &HeartPainter,
+    state: &AppState,
+    time: f64,
+    width: u32,
+    height: u32,
+    path: impl AsRef<Path>,
+) -> Result<(), RenderError> {
+    let mut state = state.clone();
+    state.seek(time);
+    render_frame(painter, &state, width, height)?.write_png(path)
+}
+
+/// Converts premultiplied RGBA pixels to straight alpha in place.
+fn unpremultiply(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        if alpha != 0 && alpha != 255 {
+            for channel in &mut pixel[..3] {
+                *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+            }
+        }
+    }
+}
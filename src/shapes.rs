@@ -0,0 +1,189 @@
+//! The shape library: every outline the heart can pulse with.
+//!
+//! Besides the parametric [`HeartShape`], an [`Icon`] can be a rounded
+//! emoji-style heart, an outline-only heart, an anatomical silhouette or any
+//! SVG path, so the same beating engine drives other icons too.
+//! [`ShapeLibrary`] looks icons up by name and lets applications register
+//! icons of their own next to the built-in ones.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+
+use druid::kurbo::{Affine, BezPath, Rect, Shape};
+
+use crate::geometry::HeartShape;
+
+/// A rounded heart with full lobes and a shallow cleft, in a 100×90 box.
+const ROUNDED_PATH: &str = "M50 90 C20 70 0 50 0 30 C0 12 12 0 27 0 C38 0 46 7 50 16 \
+                            C54 7 62 0 73 0 C88 0 100 12 100 30 C100 50 80 70 50 90 Z";
+
+/// An anatomical heart: a tilted muscular body below the aortic arch and
+/// the great vessels, in a 100×100 box.
+const ANATOMICAL_PATH: &str = "M46 20 C40 8 44 2 52 2 C58 2 60 8 58 14 L56 22 \
+                               C62 20 70 18 76 22 L80 10 C82 4 90 4 90 12 L86 28 \
+                               C96 38 98 56 88 72 C78 88 58 98 44 96 C26 94 10 80 8 62 \
+                               C6 46 14 32 28 26 L24 14 C22 8 30 6 32 12 L36 24 \
+                               C40 22 44 22 46 20 Z";
+
+/// The parsed [`ROUNDED_PATH`].
+static ROUNDED: OnceLock<BezPath> = OnceLock::new();
+/// The parsed [`ANATOMICAL_PATH`].
+static ANATOMICAL: OnceLock<BezPath> = OnceLock::new();
+
+/// An outline the heart can pulse with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Icon {
+    /// The classic two-curve heart with the given parameters.
+    Heart(HeartShape),
+    /// The classic heart drawn as an outline only, without a fill.
+    Outline(HeartShape),
+    /// A rounded, emoji-style heart.
+    Rounded,
+    /// A silhouette of an anatomical heart with its great vessels.
+    Anatomical,
+    /// Any path, e.g. loaded from SVG path data.
+    Path(Arc<BezPath>),
+}
+
+impl Icon {
+    /// Loads an icon from SVG path data, such as `M0 0 L10 0 L5 8 Z`.
+    ///
+    /// The path may use any coordinates; it is scaled to fit when drawn.
+    pub fn from_svg(data: &str) -> Result<Icon, String> {
+        let path = BezPath::from_svg(data)
+            .map_err(|err| format!("`{}` is not an SVG path: {}", data, err))?;
+        let bounds = path.bounding_box();
+        if bounds.width() <= 0.0 && bounds.height() <= 0.0 {
+            return Err(format!("the SVG path `{}` is empty", data));
+        }
+        Ok(Icon::Path(Arc::new(path)))
+    }
+
+    /// Returns the icon's outline fitted into `rect`.
+    ///
+    /// Hearts of the [`Heart`](Icon::Heart) and [`Outline`](Icon::Outline)
+    /// kinds fill `rect` exactly, like [`HeartShape::path`]; all other icons
+    /// keep their proportions and are centered in it.
+    pub fn path(&self, rect: Rect) -> BezPath {
+        match self {
+            Icon::Heart(shape) | Icon::Outline(shape) => shape.path(rect),
+            Icon::Rounded => fit(builtin(&ROUNDED, ROUNDED_PATH), rect),
+            Icon::Anatomical => fit(builtin(&ANATOMICAL, ANATOMICAL_PATH), rect),
+            Icon::Path(path) => fit(path, rect),
+        }
+    }
+
+    /// Returns whether the icon is filled, rather than only outlined.
+    pub fn is_filled(&self) -> bool {
+        !matches!(self, Icon::Outline(_))
+    }
+}
+
+impl Default for Icon {
+    fn default() -> Self {
+        Icon::Heart(HeartShape::default())
+    }
+}
+
+impl From<HeartShape> for Icon {
+    fn from(shape: HeartShape) -> Self {
+        Icon::Heart(shape)
+    }
+}
+
+/// Looks an icon up by name in the built-in library.
+impl FromStr for Icon {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ShapeLibrary::builtin().get_or_err(s)
+    }
+}
+
+/// Icons by name.
+///
+/// The built-in library holds `heart`, `outline`, `rounded` and
+/// `anatomical`. Names are matched regardless of case.
+#[derive(Clone, Debug)]
+pub struct ShapeLibrary {
+    icons: BTreeMap<String, Icon>,
+}
+
+impl ShapeLibrary {
+    /// Returns a library holding the built-in icons.
+    pub fn builtin() -> Self {
+        let mut library = ShapeLibrary {
+            icons: BTreeMap::new(),
+        };
+        library.insert("heart", Icon::Heart(HeartShape::default()));
+        library.insert("outline", Icon::Outline(HeartShape::default()));
+        library.insert("rounded", Icon::Rounded);
+        library.insert("anatomical", Icon::Anatomical);
+        library
+    }
+
+    /// Adds an icon under `name`, replacing any icon of the same name.
+    pub fn insert(&mut self, name: &str, icon: Icon) {
+        self.icons.insert(name.to_ascii_lowercase(), icon);
+    }
+
+    /// Adds an icon loaded from SVG path data under `name`.
+    pub fn insert_svg(&mut self, name: &str, data: &str) -> Result<(), String> {
+        self.insert(name, Icon::from_svg(data)?);
+        Ok(())
+    }
+
+    /// Returns the icon registered under `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&Icon> {
+        self.icons.get(&name.to_ascii_lowercase())
+    }
+
+    /// Returns the names of all icons, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.icons.keys().map(String::as_str)
+    }
+
+    /// Returns the icon registered under `name`, or an error listing the
+    /// names available.
+    pub fn get_or_err(&self, name: &str) -> Result<Icon, String> {
+        self.get(name.trim()).cloned().ok_or_else(|| {
+            let names: Vec<_> = self.names().collect();
+            format!(
+                "unknown shape `{}` (expected one of: {})",
+                name,
+                names.join(", ")
+            )
+        })
+    }
+}
+
+impl Default for ShapeLibrary {
+    fn default() -> Self {
+        ShapeLibrary::builtin()
+    }
+}
+
+/// Returns a built-in path, parsing it on first use.
+fn builtin(cell: &'static OnceLock<BezPath>, data: &str) -> &'static BezPath {
+    cell.get_or_init(|| BezPath::from_svg(data).expect("built-in shapes are valid SVG"))
+}
+
+/// Returns `path` scaled uniformly to fit into `rect` and centered in it.
+fn fit(path: &BezPath, rect: Rect) -> BezPath {
+    let bounds = path.bounding_box();
+    let scale = match (bounds.width() > 0.0, bounds.height() > 0.0) {
+        (true, true) => (rect.width() / bounds.width()).min(rect.height() / bounds.height()),
+        (true, false) => rect.width() / bounds.width(),
+        (false, true) => rect.height() / bounds.height(),
+        (false, false) => 1.0,
+    };
+
+    let mut path = path.clone();
+    path.apply_affine(
+        Affine::translate(rect.center().to_vec2())
+            * Affine::scale(scale)
+            * Affine::translate(-bounds.center().to_vec2()),
+    );
+    path
+}
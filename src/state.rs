@@ -0,0 +1,134 @@
+//! The animation model behind a beating heart.
+
+use std::sync::Arc;
+
+use druid::{Data, Lens};
+
+use crate::beat;
+use crate::clock::AnimationClock;
+use crate::timeline::Timeline;
+
+/// The state a [`HeartWidget`] animates.
+///
+/// [`HeartWidget`]: crate::HeartWidget
+#[derive(Clone, Debug, Data, Lens)]
+pub struct AppState {
+    /// Animation time in seconds.
+    pub time: f64,
+    /// The clock converting frame intervals into animation time.
+    pub clock: AnimationClock,
+    /// Beats elapsed since the animation started; the fractional part is the
+    /// phase within the current beat.
+    pub beats: f64,
+    /// Heart rate in beats per minute.
+    pub bpm: f64,
+    /// Relative size change at the peak of a beat.
+    pub amplitude: f64,
+    /// A recording the beat follows instead of running freely at `bpm`.
+    pub timeline: Option<Arc<Timeline>>,
+}
+
+impl AppState {
+    /// Creates a state at the start of the animation.
+    ///
+    /// # Arguments
+    ///
+    /// * `bpm` - The heart rate in beats per minute.
+    /// * `amplitude` - The relative size change at the peak of a beat.
+    pub fn new(bpm: f64, amplitude: f64) -> Self {
+        AppState {
+            time: 0.0,
+            clock: AnimationClock::new(),
+            beats: 0.0,
+            bpm: beat::clamp_bpm(bpm),
+            amplitude: beat::clamp_amplitude(amplitude),
+            timeline: None,
+        }
+    }
+
+    /// Makes the beat follow a recording, or run freely again with `None`,
+    /// from the current animation time on.
+    pub fn set_timeline(&mut self, timeline: Option<Arc<Timeline>>) {
+        self.timeline = timeline;
+        self.seek(self.time);
+    }
+
+    /// Advances the animation by one frame.
+    ///
+    /// With a timeline, the beat follows the recording and the clock pauses
+    /// when the recording ends.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - The real time elapsed since the previous frame in
+    ///   nanoseconds, as carried by `Event::AnimFrame`.
+    pub fn advance(&mut self, interval: u64) {
+        let dt = self.clock.tick(interval);
+        match self.timeline.clone() {
+            Some(timeline) => {
+                let end = timeline.duration();
+                if self.time < end && self.time + dt >= end {
+                    self.clock.set_paused(true);
+                }
+                self.seek((self.time + dt).min(end.max(self.time)));
+            }
+            None => {
+                self.time += dt;
+                self.beats += beat::beats_in(dt, self.bpm);
+            }
+        }
+    }
+
+    /// Jumps to a point in time.
+    ///
+    /// With a timeline, the beat and rate are taken from the recording;
+    /// otherwise the heart is treated as if it had been beating at its
+    /// current rate since the start of the animation.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The animation time to jump to, in seconds.
+    pub fn seek(&mut self, time: f64) {
+        self.time = time;
+        match &self.timeline {
+            Some(timeline) => {
+                self.beats = timeline.beats_at(time);
+                self.bpm = timeline.bpm_at(time);
+            }
+            None => self.beats = beat::beats_in(time, self.bpm),
+        }
+    }
+
+    /// Shifts the beat position by less than half a beat so the heart is at
+    /// `peak_phase` now, e.g. when a sensor reports a beat.
+    ///
+    /// # Arguments
+    ///
+    /// * `peak_phase` - The phase of the beat envelope's strongest
+    ///   contraction.
+    pub fn sync_beat(&mut self, peak_phase: f64) {
+        self.beats = (self.beats - peak_phase).round() + peak_phase;
+    }
+
+    /// Returns the number of beats elapsed at another animation time.
+    ///
+    /// Without a timeline, the heart is assumed to have kept its current
+    /// rate since then.
+    pub fn beats_at(&self, time: f64) -> f64 {
+        match &self.timeline {
+            Some(timeline) => timeline.beats_at(time),
+            None => self.beats - beat::beats_in(self.time - time, self.bpm),
+        }
+    }
+
+    /// Returns the phase within the current beat, in `[0, 1)`.
+    pub fn phase(&self) -> f64 {
+        beat::phase(self.beats)
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        AppState::new(beat::DEFAULT_BPM, beat::DEFAULT_AMPLITUDE)
+    }
+}
@@ -0,0 +1,340 @@
+//! SVG export of the heart.
+//!
+//! A static SVG holds the heart as it looks at one moment. An animated SVG
+//! holds the resting heart together with keyframes sampled from the beat
+//! envelope, so browsers play the same beat, and the same color ramp,
+//! without running any Rust.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use druid::{
+    piet::{LineCap, LineJoin},
+    Color, Size,
+};
+
+use crate::beat;
+use crate::paint::{HeartPainter, PaintOrder, StrokeAlign};
+use crate::state::AppState;
+
+/// Number of keyframes per beat used unless configured otherwise.
+pub const DEFAULT_SAMPLES_PER_BEAT: usize = 48;
+
+/// The mechanism an animated SVG uses to play the beat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SvgAnimation {
+    /// An `<animateTransform>` element, which also plays when the SVG is
+    /// embedded as an image.
+    Smil,
+    /// A CSS `@keyframes` rule, which is easy to restyle from a web page.
+    Css,
+}
+
+impl FromStr for SvgAnimation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "smil" => Ok(SvgAnimation::Smil),
+            "css" => Ok(SvgAnimation::Css),
+            _ => Err(format!(
+                "unknown SVG animation `{}` (expected smil or css)",
+                s
+            )),
+        }
+    }
+}
+
+/// Describes the SVG document to produce.
+#[derive(Clone, Debug)]
+pub struct SvgOptions {
+    /// Width of the document in pixels.
+    pub width: f64,
+    /// Height of the document in pixels.
+    pub height: f64,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            width: 256.0,
+            height: 256.0,
+        }
+    }
+}
+
+/// Returns an SVG document showing the heart as it looks in `state`.
+///
+/// # Arguments
+///
+/// * `painter` - Describes how the heart is drawn.
+/// * `state` - The animation model at the moment to draw.
+/// * `options` - The size of the document.
+pub fn static_svg(painter: &HeartPainter, state: &AppState, options: &SvgOptions) -> String {
+    let size = Size::new(options.width, options.height);
+    let mut svg = open_document(painter, options);
+    let path = painter.path(size, state).to_svg();
+    let fill = painter.fill_at(painter.pulse(state));
+    write_clip(&mut svg, painter, &path);
+    let _ = writeln!(
+        svg,
+        "  <path d=\"{}\" {}/>",
+        path,
+        paint_attributes(painter, fill)
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Returns an SVG document in which the heart beats forever.
+///
+/// # Arguments
+///
+/// * `painter` - Describes how the heart is drawn and shapes each beat.
+/// * `state` - The animation model; its heart rate and amplitude are used.
+/// * `options` - The size of the document.
+/// * `animation` - The mechanism used to play the beat.
+/// * `samples_per_beat` - The number of keyframes sampled from the beat
+///   envelope; more keyframes follow sharp envelopes more closely.
+pub fn animated_svg(
+    painter: &HeartPainter,
+    state: &AppState,
+    options: &SvgOptions,
+    animation: SvgAnimation,
+    samples_per_beat: usize,
+) -> String {
+    let size = Size::new(options.width, options.height);
+    let center = (options.width / 2.0, options.height / 2.0);
+    let duration = 60.0 / beat::clamp_bpm(state.bpm);
+
+    // Sample the scale and fill over one beat; the final keyframe wraps to
+    // the first
+    let samples = samples_per_beat.max(2);
+    let mut sample_state = state.clone();
+    let keyframes: Vec<(f64, f64, Color)> = (0..=samples)
+        .map(|i| {
+            let offset = i as f64 / samples as f64;
+            sample_state.beats = offset;
+            let fill = painter.fill_at(painter.pulse(&sample_state));
+            (offset, painter.scale(&sample_state), fill)
+        })
+        .collect();
+    let animate_fill = painter.color_ramp.is_some() && painter.shape.is_filled();
+
+    // The keyframes scale the heart at rest
+    let mut rest = state.clone();
+    rest.amplitude = 0.0;
+    let path = painter.path(size, &rest).to_svg();
+    let attributes = paint_attributes(painter, keyframes[0].2);
+
+    let mut svg = open_document(painter, options);
+    write_clip(&mut svg, painter, &path);
+    match animation {
+        SvgAnimation::Smil => {
+            let values: Vec<String> = keyframes.iter().map(|(_, s, _)| fmt_number(*s)).collect();
+            let times: Vec<String> = keyframes.iter().map(|(t, _, _)| fmt_number(*t)).collect();
+            let _ = writeln!(
+                svg,
+                "  <g transform=\"translate({} {})\">",
+                fmt_number(center.0),
+                fmt_number(center.1)
+            );
+            svg.push_str("    <g>\n");
+            let _ = writeln!(
+                svg,
+                "      <animateTransform attributeName=\"transform\" type=\"scale\" \
+                 dur=\"{}s\" repeatCount=\"indefinite\" calcMode=\"linear\" \
+                 values=\"{}\" keyTimes=\"{}\"/>",
+                fmt_number(duration),
+                values.join(";"),
+                times.join(";")
+            );
+            let _ = write!(
+                svg,
+                "      <path transform=\"translate({} {})\" d=\"{}\" {}",
+                fmt_number(-center.0),
+                fmt_number(-center.1),
+                path,
+                attributes
+            );
+            if animate_fill {
+                let fills: Vec<String> = keyframes.iter().map(|(_, _, f)| fmt_color(*f)).collect();
+                let _ = writeln!(
+                    svg,
+                    ">\n        <animate attributeName=\"fill\" dur=\"{}s\" \
+                     repeatCount=\"indefinite\" calcMode=\"linear\" values=\"{}\" \
+                     keyTimes=\"{}\"/>\n      </path>",
+                    fmt_number(duration),
+                    fills.join(";"),
+                    times.join(";")
+                );
+            } else {
+                svg.push_str("/>\n");
+            }
+            svg.push_str("    </g>\n  </g>\n");
+        }
+        SvgAnimation::Css => {
+            svg.push_str("  <style>\n    @keyframes heartbeat {\n");
+            for (offset, scale, fill) in &keyframes {
+                let _ = write!(
+                    svg,
+                    "      {}% {{ transform: scale({}); ",
+                    fmt_number(offset * 100.0),
+                    fmt_number(*scale)
+                );
+                if animate_fill {
+                    let _ = write!(svg, "fill: {}; ", fmt_color(*fill));
+                }
+                svg.push_str("}\n");
+            }
+            svg.push_str("    }\n");
+            let _ = writeln!(
+                svg,
+                "    .heart {{ transform-origin: {}px {}px; \
+                 animation: heartbeat {}s linear infinite; }}",
+                fmt_number(center.0),
+                fmt_number(center.1),
+                fmt_number(duration)
+            );
+            svg.push_str("  </style>\n");
+            let _ = writeln!(
+                svg,
+                "  <path class=\"heart\" d=\"{}\" {}/>",
+                path, attributes
+            );
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Starts a document with the given size and the painter's background.
+fn open_document(painter: &HeartPainter, options: &SvgOptions) -> String {
+    let width = fmt_number(options.width);
+    let height = fmt_number(options.height);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+         viewBox=\"0 0 {0} {1}\">\n",
+        width, height
+    );
+    if let Some(background) = painter.background {
+        let _ = writeln!(
+            svg,
+            "  <rect width=\"100%\" height=\"100%\" {}/>",
+            color_attributes("fill", background)
+        );
+    }
+    svg
+}
+
+/// Id of the clip path keeping an inside outline within the heart.
+const CLIP_ID: &str = "heart-outline";
+
+/// Writes the clip path an inside outline needs, if the painter has one.
+///
+/// SVG cannot align strokes, so an outline inside or outside the heart is
+/// drawn twice as wide and cut in half: by clipping it to the heart's path,
+/// or by painting the fill over it.
+fn write_clip(svg: &mut String, painter: &HeartPainter, path: &str) {
+    if painter.stroke_width > 0.0 && painter.stroke_options.align == StrokeAlign::Inside {
+        let _ = writeln!(
+            svg,
+            "  <defs><clipPath id=\"{}\"><path d=\"{}\"/></clipPath></defs>",
+            CLIP_ID, path
+        );
+    }
+}
+
+/// Returns the fill and stroke attributes matching the painter, filling
+/// the heart with `fill` unless its icon is only outlined.
+///
+/// As in the widget, the outline follows the configured paint order,
+/// alignment, joins, caps and dashes, and does not scale with the beat.
+/// Outside outlines rely on an opaque fill to hide their inner half.
+fn paint_attributes(painter: &HeartPainter, fill: Color) -> String {
+    let mut attributes = if painter.shape.is_filled() {
+        color_attributes("fill", fill)
+    } else {
+        "fill=\"none\" ".to_string()
+    };
+    if painter.stroke_width <= 0.0 {
+        attributes.push_str("stroke=\"none\" ");
+        return attributes;
+    }
+
+    let options = &painter.stroke_options;
+    let (width, stroke_first) = match options.align {
+        StrokeAlign::Centered => (painter.stroke_width, false),
+        StrokeAlign::Inside => {
+            let _ = write!(attributes, "clip-path=\"url(#{})\" ", CLIP_ID);
+            (2.0 * painter.stroke_width, false)
+        }
+        StrokeAlign::Outside => (2.0 * painter.stroke_width, true),
+    };
+    let _ = write!(
+        attributes,
+        "{}stroke-width=\"{}\" ",
+        color_attributes("stroke", painter.stroke),
+        fmt_number(width)
+    );
+    if stroke_first || painter.paint_order == PaintOrder::StrokeFirst {
+        attributes.push_str("paint-order=\"stroke\" ");
+    }
+
+    match options.join {
+        LineJoin::Miter { limit } => {
+            let _ = write!(attributes, "stroke-miterlimit=\"{}\" ", fmt_number(limit));
+        }
+        LineJoin::Round => attributes.push_str("stroke-linejoin=\"round\" "),
+        LineJoin::Bevel => attributes.push_str("stroke-linejoin=\"bevel\" "),
+    }
+    match options.cap {
+        LineCap::Butt => {}
+        LineCap::Round => attributes.push_str("stroke-linecap=\"round\" "),
+        LineCap::Square => attributes.push_str("stroke-linecap=\"square\" "),
+    }
+    if !options.dash.is_empty() {
+        let dash: Vec<String> = options.dash.iter().map(|&len| fmt_number(len)).collect();
+        let _ = write!(attributes, "stroke-dasharray=\"{}\" ", dash.join(" "));
+        if options.dash_offset != 0.0 {
+            let _ = write!(
+                attributes,
+                "stroke-dashoffset=\"{}\" ",
+                fmt_number(options.dash_offset)
+            );
+        }
+    }
+    attributes.push_str("vector-effect=\"non-scaling-stroke\" ");
+    attributes
+}
+
+/// Returns a color attribute and, for translucent colors, its opacity.
+fn color_attributes(name: &str, color: Color) -> String {
+    let mut attributes = format!("{}=\"{}\" ", name, fmt_color(color));
+    let (_, _, _, a) = color.as_rgba8();
+    if a != 255 {
+        let _ = write!(
+            attributes,
+            "{}-opacity=\"{}\" ",
+            name,
+            fmt_number(a as f64 / 255.0)
+        );
+    }
+    attributes
+}
+
+/// Formats a color as `#rrggbb`, leaving out its opacity.
+fn fmt_color(color: Color) -> String {
+    let (r, g, b, _) = color.as_rgba8();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Formats a number compactly, with at most four decimal places.
+fn fmt_number(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" | "" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
@@ -0,0 +1,173 @@
+//! Theming the heart through druid's [`Env`].
+//!
+//! A [`HeartWidget`] reads its colors and outline width from the keys in
+//! this module whenever the environment sets them, so the host application
+//! can restyle every heart at once, e.g. with [`Theme::apply`] in
+//! [`AppLauncher::configure_env`] or a [`WidgetExt::env_scope`]. Values set
+//! explicitly on a [`HeartBuilder`] take precedence over the environment.
+//!
+//! [`HeartWidget`]: crate::HeartWidget
+//! [`HeartBuilder`]: crate::HeartBuilder
+//! [`AppLauncher::configure_env`]: druid::AppLauncher::configure_env
+//! [`WidgetExt::env_scope`]: druid::WidgetExt::env_scope
+
+use std::fmt;
+use std::str::FromStr;
+
+use druid::{Color, Env, Key};
+
+use crate::paint::{HeartPainter, DEFAULT_STROKE_WIDTH};
+
+/// The color the heart is filled with.
+pub const FILL: Key<Color> = Key::new("beating_heart.theme.fill");
+/// The color of the heart's outline.
+pub const STROKE: Key<Color> = Key::new("beating_heart.theme.stroke");
+/// The width of the heart's outline; zero disables it.
+pub const STROKE_WIDTH: Key<f64> = Key::new("beating_heart.theme.stroke-width");
+/// The color the widget's background is filled with.
+pub const BACKGROUND: Key<Color> = Key::new("beating_heart.theme.background");
+
+/// The built-in themes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Theme {
+    /// A crimson heart on a white background.
+    #[default]
+    Light,
+    /// A bright rose heart with a pale outline on a charcoal background.
+    Dark,
+    /// A pure red heart with a thick white outline on black.
+    HighContrast,
+}
+
+impl Theme {
+    /// Every built-in theme, in the order they are presented to users.
+    pub const ALL: [Theme; 3] = [Theme::Light, Theme::Dark, Theme::HighContrast];
+
+    /// Returns the name used to select the theme in options and files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::HighContrast => "high-contrast",
+        }
+    }
+
+    /// Returns the color the heart is filled with.
+    pub fn fill(self) -> Color {
+        match self {
+            Theme::Light => Color::rgb8(220, 20, 60),
+            Theme::Dark => Color::rgb8(255, 82, 110),
+            Theme::HighContrast => Color::rgb8(255, 0, 0),
+        }
+    }
+
+    /// Returns the color of the heart's outline.
+    pub fn stroke(self) -> Color {
+        match self {
+            Theme::Light => Color::rgb8(60, 10, 20),
+            Theme::Dark => Color::rgb8(250, 225, 230),
+            Theme::HighContrast => Color::rgb8(255, 255, 255),
+        }
+    }
+
+    /// Returns the width of the heart's outline.
+    pub fn stroke_width(self) -> f64 {
+        match self {
+            Theme::Light | Theme::Dark => DEFAULT_STROKE_WIDTH,
+            Theme::HighContrast => 2.0 * DEFAULT_STROKE_WIDTH,
+        }
+    }
+
+    /// Returns the color the widget's background is filled with.
+    pub fn background(self) -> Color {
+        match self {
+            Theme::Light => Color::rgb8(255, 255, 255),
+            Theme::Dark => Color::rgb8(32, 32, 36),
+            Theme::HighContrast => Color::rgb8(0, 0, 0),
+        }
+    }
+
+    /// Sets the theme's values on every key of this module.
+    pub fn apply(self, env: &mut Env) {
+        env.set(FILL, self.fill());
+        env.set(STROKE, self.stroke());
+        env.set(STROKE_WIDTH, self.stroke_width());
+        env.set(BACKGROUND, self.background());
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Theme::ALL
+            .into_iter()
+            .find(|theme| theme.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = Theme::ALL.iter().map(|t| t.name()).collect();
+                format!(
+                    "unknown theme `{}` (expected one of: {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Which parts of a painter follow the keys of this module rather than
+/// the values they were configured with.
+///
+/// A [`HeartBuilder`] tracks these alongside its painter, and
+/// [`SET_PAINTER`] carries them with a new painter, so values set
+/// explicitly keep precedence over the environment after a reload.
+///
+/// [`SET_PAINTER`]: crate::SET_PAINTER
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Themed {
+    /// Whether the fill follows [`FILL`].
+    pub fill: bool,
+    /// Whether the outline's color follows [`STROKE`].
+    pub stroke: bool,
+    /// Whether the outline's width follows [`STROKE_WIDTH`].
+    pub stroke_width: bool,
+    /// Whether the background follows [`BACKGROUND`].
+    pub background: bool,
+}
+
+impl Themed {
+    /// Returns `painter` with every themed part taken from `env`, where the
+    /// environment sets it.
+    pub fn resolve(&self, painter: &HeartPainter, env: &Env) -> HeartPainter {
+        let mut painter = painter.clone();
+        if let (true, Ok(fill)) = (self.fill, env.try_get(FILL)) {
+            painter.fill = fill;
+        }
+        if let (true, Ok(stroke)) = (self.stroke, env.try_get(STROKE)) {
+            painter.stroke = stroke;
+        }
+        if let (true, Ok(width)) = (self.stroke_width, env.try_get(STROKE_WIDTH)) {
+            painter.stroke_width = width.max(0.0);
+        }
+        if let (true, Ok(background)) = (self.background, env.try_get(BACKGROUND)) {
+            painter.background = Some(background);
+        }
+        painter
+    }
+}
+
+impl Default for Themed {
+    fn default() -> Self {
+        Themed {
+            fill: true,
+            stroke: true,
+            stroke_width: true,
+            background: true,
+        }
+    }
+}
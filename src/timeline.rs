@@ -0,0 +1,468 @@
+//! Recorded heart-rate data driving the beat.
+//!
+//! A timeline is loaded either from heart-rate samples (a timestamp and a
+//! rate in beats per minute) or from the timestamps of individual R peaks.
+//! Both are turned into a mapping from playback time to beats elapsed, so
+//! the heart contracts exactly on the recorded beats.
+//!
+//! CSV files hold one record per line: `time,bpm` for heart-rate samples or
+//! just `time` for R peaks. A header line and lines starting with `#` are
+//! skipped. JSON files hold an object with either a `rate` list of
+//! `{"time": .., "bpm": ..}` objects or a `peaks` list of timestamps.
+//! Timestamps are in seconds and playback starts at the first of them.
+//! Exports from fitness watches are read by [`fitness`](crate::fitness).
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::beat;
+use crate::envelope::{self, BeatEnvelope};
+use crate::fitness;
+
+/// An error raised while loading a timeline.
+#[derive(Debug)]
+pub enum TimelineError {
+    /// Reading the file failed.
+    Io(io::Error),
+    /// The file's extension is not one of the supported formats.
+    UnknownFormat(PathBuf),
+    /// The file is not valid CSV or JSON, or has unexpected contents.
+    Parse(String),
+    /// The records do not describe a usable timeline.
+    Invalid(String),
+}
+
+impl fmt::Display for TimelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimelineError::Io(err) => write!(f, "reading failed: {}", err),
+            TimelineError::UnknownFormat(path) => {
+                write!(
+                    f,
+                    "`{}` is not a .csv, .json, .fit, .tcx or .gpx file",
+                    path.display()
+                )
+            }
+            TimelineError::Parse(err) => f.write_str(err),
+            TimelineError::Invalid(err) => f.write_str(err),
+        }
+    }
+}
+
+impl std::error::Error for TimelineError {}
+
+impl From<io::Error> for TimelineError {
+    fn from(err: io::Error) -> Self {
+        TimelineError::Io(err)
+    }
+}
+
+/// A heart rate measured at one moment.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct RateSample {
+    /// Time of the measurement, in seconds.
+    pub time: f64,
+    /// Heart rate in beats per minute, held until the next sample.
+    pub bpm: f64,
+}
+
+/// The contents of a JSON timeline file.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TimelineFile {
+    Rate(Vec<RateSample>),
+    Peaks(Vec<f64>),
+}
+
+/// A recording mapping playback time to beats elapsed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timeline {
+    /// Points of the mapping as (time, beats); beats change linearly in
+    /// between.
+    knots: Vec<(f64, f64)>,
+    /// Beats per second before the first point.
+    lead_rate: f64,
+    /// Beats per second after the last point.
+    tail_rate: f64,
+    /// Phase within the beat that lands on each recorded R peak.
+    peak_phase: f64,
+}
+
+impl Timeline {
+    /// Creates a timeline from heart-rate samples.
+    ///
+    /// Each rate holds until the next sample. Rates outside the range the
+    /// animation accepts are clamped to it.
+    pub fn from_rate(samples: &[RateSample]) -> Result<Timeline, TimelineError> {
+        if samples.len() < 2 {
+            return Err(TimelineError::Invalid(
+                "the timeline needs at least two samples".to_string(),
+            ));
+        }
+        let first = &samples[0];
+        check_times(samples.iter().map(|sample| sample.time))?;
+        if let Some(sample) = samples.iter().find(|sample| !sample.bpm.is_finite()) {
+            return Err(TimelineError::Invalid(format!(
+                "the rate at {} s is not a number",
+                sample.time
+            )));
+        }
+
+        let rate = |sample: &RateSample| beat::beats_in(1.0, beat::clamp_bpm(sample.bpm));
+        let mut beats = 0.0;
+        let mut knots = vec![(0.0, 0.0)];
+        for pair in samples.windows(2) {
+            beats += rate(&pair[0]) * (pair[1].time - pair[0].time);
+            knots.push((pair[1].time - first.time, beats));
+        }
+
+        Ok(Timeline {
+            knots,
+            lead_rate: rate(first),
+            tail_rate: rate(&samples[samples.len() - 1]),
+            peak_phase: 0.0,
+        })
+    }
+
+    /// Creates a timeline from the timestamps of R peaks, one per beat.
+    ///
+    /// Every peak still lands on a beat, however close together or far apart
+    /// the peaks are; only the rates [`Timeline::bpm_at`] reports, including
+    /// those extrapolated before the first peak and after the last, are
+    /// clamped to the range the animation accepts.
+    pub fn from_peaks(peaks: &[f64]) -> Result<Timeline, TimelineError> {
+        if peaks.len() < 2 {
+            return Err(TimelineError::Invalid(
+                "the timeline needs at least two peaks".to_string(),
+            ));
+        }
+        check_times(peaks.iter().copied())?;
+
+        let knots: Vec<(f64, f64)> = peaks
+            .iter()
+            .enumerate()
+            .map(|(i, peak)| (peak - peaks[0], i as f64))
+            .collect();
+        let interval = |a: f64, b: f64| beat::beats_in(1.0, beat::clamp_bpm(60.0 / (b - a)));
+        Ok(Timeline {
+            lead_rate: interval(peaks[0], peaks[1]),
+            tail_rate: interval(peaks[peaks.len() - 2], peaks[peaks.len() - 1]),
+            knots,
+            peak_phase: 0.0,
+        })
+    }
+
+    /// Loads a timeline from a CSV, JSON, FIT, TCX or GPX file.
+    ///
+    /// The format is chosen by the file's extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Timeline, TimelineError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        if extension.as_deref() == Some("fit") {
+            return fitness::parse_fit(&fs::read(path)?);
+        }
+        let text = fs::read_to_string(path)?;
+
+        match extension.as_deref() {
+            Some("csv") => Timeline::parse_csv(&text),
+            Some("tcx") => fitness::parse_tcx(&text),
+            Some("gpx") => fitness::parse_gpx(&text),
+            Some("json") => {
+                match serde_json::from_str(&text)
+                    .map_err(|err| TimelineError::Parse(err.to_string()))?
+                {
+                    TimelineFile::Rate(samples) => Timeline::from_rate(&samples),
+                    TimelineFile::Peaks(peaks) => Timeline::from_peaks(&peaks),
+                }
+            }
+            _ => Err(TimelineError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Parses CSV records of `time,bpm` samples or `time` peaks.
+    pub fn parse_csv(text: &str) -> Result<Timeline, TimelineError> {
+        let mut records: Vec<Vec<f64>> = Vec::new();
+        let mut first = true;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Result<Vec<f64>, _> =
+                line.split(',').map(|field| field.trim().parse()).collect();
+            match fields {
+                Ok(fields) => records.push(fields),
+                // The first line may be a header
+                Err(_) if first => {}
+                Err(_) => {
+                    return Err(TimelineError::Parse(format!(
+                        "line {}: `{}` is not a record of numbers",
+                        index + 1,
+                        line
+                    )))
+                }
+            }
+            first = false;
+        }
+
+        let columns = records.first().map_or(0, Vec::len);
+        if let Some(index) = records.iter().position(|record| record.len() != columns) {
+            return Err(TimelineError::Parse(format!(
+                "record {} has {} fields instead of {}",
+                index + 1,
+                records[index].len(),
+                columns
+            )));
+        }
+        match columns {
+            1 => Timeline::from_peaks(&records.iter().map(|r| r[0]).collect::<Vec<_>>()),
+            2 => Timeline::from_rate(
+                &records
+                    .iter()
+                    .map(|r| RateSample {
+                        time: r[0],
+                        bpm: r[1],
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            0 => Err(TimelineError::Invalid(
+                "the timeline has no records".to_string(),
+            )),
+            _ => Err(TimelineError::Parse(format!(
+                "expected `time,bpm` or `time` records, found {} fields",
+                columns
+            ))),
+        }
+    }
+
+    /// Shifts the beats so every recorded R peak lands on the envelope's
+    /// strongest contraction.
+    pub fn align_to(&mut self, envelope: &dyn BeatEnvelope) {
+        self.peak_phase = envelope::peak_phase(envelope);
+    }
+
+    /// Returns the playback time of the last record, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.knots[self.knots.len() - 1].0
+    }
+
+    /// Returns the number of beats elapsed at a playback time.
+    pub fn beats_at(&self, time: f64) -> f64 {
+        let beats = match self.segment(time) {
+            Segment::Before => self.knots[0].1 + (time - self.knots[0].0) * self.lead_rate,
+            Segment::After => {
+                let (last_time, last_beats) = self.knots[self.knots.len() - 1];
+                last_beats + (time - last_time) * self.tail_rate
+            }
+            Segment::Between(i) => {
+                let (t0, b0) = self.knots[i];
+                let (t1, b1) = self.knots[i + 1];
+                b0 + (b1 - b0) * (time - t0) / (t1 - t0)
+            }
+        };
+        beats + self.peak_phase
+    }
+
+    /// Returns the heart rate at a playback time, in beats per minute,
+    /// clamped to the range the animation accepts.
+    pub fn bpm_at(&self, time: f64) -> f64 {
+        let rate = match self.segment(time) {
+            Segment::Before => self.lead_rate,
+            Segment::After => self.tail_rate,
+            Segment::Between(i) => {
+                let (t0, b0) = self.knots[i];
+                let (t1, b1) = self.knots[i + 1];
+                (b1 - b0) / (t1 - t0)
+            }
+        };
+        beat::clamp_bpm(rate * 60.0)
+    }
+
+    /// Finds the stretch of the mapping containing a playback time.
+    fn segment(&self, time: f64) -> Segment {
+        // A NaN time would otherwise fall before every point but the first
+        if time.is_nan() || time < self.knots[0].0 {
+            return Segment::Before;
+        }
+        match self.knots.partition_point(|&(t, _)| t <= time) {
+            i if i >= self.knots.len() => Segment::After,
+            i => Segment::Between(i - 1),
+        }
+    }
+}
+
+/// A stretch of a timeline's mapping.
+enum Segment {
+    /// Before the first point.
+    Before,
+    /// Between a point and the next.
+    Between(usize),
+    /// After the last point.
+    After,
+}
+
+/// Checks that timestamps are finite and strictly increasing.
+fn check_times(times: impl Iterator<Item = f64>) -> Result<(), TimelineError> {
+    let mut previous = f64::NEG_INFINITY;
+    for time in times {
+        if !time.is_finite() {
+            return Err(TimelineError::Invalid(format!(
+                "`{}` is not a timestamp",
+                time
+            )));
+        }
+        if time <= previous {
+            return Err(TimelineError::Invalid(format!(
+                "timestamps must increase, but {} follows {}",
+                time, previous
+            )));
+        }
+        previous = time;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::EnvelopeProfile;
+
+    /// Returns heart-rate samples from (time, bpm) pairs.
+    fn samples(pairs: &[(f64, f64)]) -> Vec<RateSample> {
+        pairs
+            .iter()
+            .map(|&(time, bpm)| RateSample { time, bpm })
+            .collect()
+    }
+
+    #[test]
+    fn rates_hold_until_the_next_sample() {
+        let timeline =
+            Timeline::from_rate(&samples(&[(10.0, 60.0), (20.0, 120.0), (30.0, 90.0)])).unwrap();
+        assert_eq!(timeline.duration(), 20.0);
+        assert_eq!(timeline.beats_at(10.0), 10.0);
+        assert_eq!(timeline.beats_at(20.0), 30.0);
+        assert_eq!(timeline.bpm_at(5.0), 60.0);
+        assert_eq!(timeline.bpm_at(15.0), 120.0);
+        // The first and last rates carry on beyond the recording
+        assert_eq!(timeline.beats_at(-1.0), -1.0);
+        assert_eq!(timeline.bpm_at(25.0), 90.0);
+    }
+
+    #[test]
+    fn rates_are_clamped() {
+        let timeline = Timeline::from_rate(&samples(&[(0.0, 1000.0), (1.0, 1.0)])).unwrap();
+        assert!((timeline.bpm_at(0.5) - beat::MAX_BPM).abs() < 1e-9);
+        assert!((timeline.bpm_at(2.0) - beat::MIN_BPM).abs() < 1e-9);
+    }
+
+    #[test]
+    fn peaks_mark_whole_beats() {
+        let timeline = Timeline::from_peaks(&[1.0, 2.0, 2.5, 3.5]).unwrap();
+        assert_eq!(timeline.duration(), 2.5);
+        assert_eq!(timeline.beats_at(0.0), 0.0);
+        assert_eq!(timeline.beats_at(1.25), 1.5);
+        assert_eq!(timeline.bpm_at(1.25), 120.0);
+        assert_eq!(timeline.bpm_at(-1.0), 60.0);
+        assert_eq!(timeline.bpm_at(3.0), 60.0);
+        assert_eq!(timeline.bpm_at(f64::NAN), 60.0);
+    }
+
+    #[test]
+    fn bad_records_are_rejected() {
+        assert!(matches!(
+            Timeline::from_peaks(&[1.0]),
+            Err(TimelineError::Invalid(_))
+        ));
+        assert!(matches!(
+            Timeline::from_peaks(&[1.0, 1.0]),
+            Err(TimelineError::Invalid(_))
+        ));
+        assert!(matches!(
+            Timeline::from_rate(&samples(&[(0.0, 60.0), (1.0, f64::NAN)])),
+            Err(TimelineError::Invalid(_))
+        ));
+        assert!(matches!(
+            Timeline::from_rate(&[]),
+            Err(TimelineError::Invalid(_))
+        ));
+        assert!(matches!(
+            Timeline::from_rate(&samples(&[(0.0, 60.0)])),
+            Err(TimelineError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn rates_between_peaks_are_clamped() {
+        let timeline = Timeline::from_peaks(&[0.0, 0.1, 10.0]).unwrap();
+        assert_eq!(timeline.bpm_at(-1.0), beat::MAX_BPM);
+        assert_eq!(timeline.bpm_at(0.05), beat::MAX_BPM);
+        assert_eq!(timeline.bpm_at(5.0), beat::MIN_BPM);
+        assert_eq!(timeline.bpm_at(11.0), beat::MIN_BPM);
+    }
+
+    #[test]
+    fn alignment_puts_peaks_on_the_strongest_contraction() {
+        let mut timeline = Timeline::from_peaks(&[0.0, 1.0]).unwrap();
+        let envelope = EnvelopeProfile::LubDub;
+        timeline.align_to(&envelope);
+        let peak = envelope::peak_phase(&envelope);
+        assert_eq!(timeline.beats_at(0.0), peak);
+        assert_eq!(timeline.beats_at(1.0), 1.0 + peak);
+    }
+
+    #[test]
+    fn csv_holds_rates_or_peaks() {
+        let rate = Timeline::parse_csv("time,bpm\n# resting\n0,60\n10, 120\n\n20,60\n").unwrap();
+        assert_eq!(rate.duration(), 20.0);
+        assert_eq!(rate.bpm_at(15.0), 120.0);
+
+        let peaks = Timeline::parse_csv("0\n0.5\n1.5\n").unwrap();
+        assert_eq!(peaks.beats_at(1.0), 1.5);
+    }
+
+    #[test]
+    fn malformed_csv_is_rejected() {
+        for text in ["0,60\nten,70\n", "0,60\n1\n", "0,60,1\n1,60,1\n"] {
+            assert!(
+                matches!(Timeline::parse_csv(text), Err(TimelineError::Parse(_))),
+                "{:?} should not parse",
+                text
+            );
+        }
+        assert!(matches!(
+            Timeline::parse_csv("time,bpm\n"),
+            Err(TimelineError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn json_files_load_by_extension() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!(
+            "beating-heart-timeline-{}.json",
+            std::process::id()
+        ));
+        fs::write(
+            &path,
+            r#"{"rate": [{"time": 0, "bpm": 60}, {"time": 5, "bpm": 90}]}"#,
+        )
+        .unwrap();
+        let timeline = Timeline::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(timeline.unwrap().duration(), 5.0);
+
+        let path = dir.join("beating-heart-timeline.txt");
+        assert!(matches!(
+            Timeline::load(&path),
+            Err(TimelineError::UnknownFormat(_) | TimelineError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,600 @@
+//! The beating heart widget.
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+use druid::{
+    kurbo::{BezPath, Point, Shape},
+    BoxConstraints, Color, Cursor, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, RenderContext, Selector, Size, UpdateCtx, Widget,
+};
+
+use crate::beat;
+use crate::color::ColorRamp;
+use crate::envelope::{self, BeatEnvelope};
+use crate::events::{BeatEvent, BeatTracker};
+use crate::paint::{HeartPainter, PaintOrder, StrokeOptions};
+use crate::shapes::Icon;
+use crate::state::AppState;
+use crate::style::HeartStyle;
+use crate::theme::{self, Theme, Themed};
+
+/// Replaces the painter of every [`HeartWidget`] the command reaches, e.g.
+/// after a configuration file was reloaded, along with which of its parts
+/// follow the [`Env`]'s theme, as [`HeartBuilder::themed`] returns them.
+pub const SET_PAINTER: Selector<(HeartPainter, Themed)> =
+    Selector::new("beating_heart.set-painter");
+
+/// Changes the fill color of every [`HeartWidget`] the command reaches,
+/// e.g. when a show controller sets it over OSC.
+pub const SET_FILL: Selector<Color> = Selector::new("beating_heart.set-fill");
+
+/// Sets the heart rate, in beats per minute, of every [`HeartWidget`] the
+/// command reaches, e.g. when a live source reports a new rate.
+pub const SET_BPM: Selector<f64> = Selector::new("beating_heart.set-bpm");
+
+/// Sets the relative size change at the peak of a beat of every
+/// [`HeartWidget`] the command reaches, e.g. when a show controller sets it
+/// over OSC.
+pub const SET_AMPLITUDE: Selector<f64> = Selector::new("beating_heart.set-amplitude");
+
+/// Tells every [`HeartWidget`] the command reaches that a beat happened
+/// just now, so it contracts at once.
+pub const BEAT: Selector = Selector::new("beating_heart.beat");
+
+/// Notification a [`HeartWidget`] submits whenever its animation passes a
+/// stage of a beat. It bubbles up to the widget's ancestors, where a
+/// [`Controller`] can react to it.
+///
+/// [`Controller`]: druid::widget::Controller
+pub const BEAT_EVENT: Selector<BeatEvent> = Selector::new("beating_heart.beat-event");
+
+/// Notification a [`HeartWidget`] submits when it is clicked, i.e. the left
+/// mouse button is pressed and released inside the heart's outline.
+pub const CLICKED: Selector = Selector::new("beating_heart.clicked");
+
+/// Color painted over the heart while the mouse hovers over it, unless
+/// configured otherwise.
+pub const DEFAULT_HOVER_COLOR: Color = Color::rgba8(255, 255, 255, 64);
+
+/// How far a squeeze presses the heart in, as a fraction of its size.
+const SQUEEZE_DEPTH: f64 = 0.12;
+/// Time a squeeze takes to press in fully or spring back, in seconds.
+const SQUEEZE_TIME: f64 = 0.12;
+
+/// A function called with every [`BeatEvent`] of a [`HeartWidget`].
+type BeatCallback = Rc<dyn Fn(&BeatEvent)>;
+
+/// A function called whenever a [`HeartWidget`] is clicked.
+type ClickCallback = Rc<dyn Fn(&mut AppState)>;
+
+/// What clicking a [`HeartWidget`] does to the heart.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClickAction {
+    /// Nothing; the click is only reported.
+    Ignore,
+    /// The heart contracts at once, as on a [`BEAT`] command.
+    #[default]
+    Beat,
+    /// The heart is squeezed in while the button is held and springs back
+    /// when it is released.
+    Squeeze,
+}
+
+/// A widget that draws a heart pulsing to the beat of an [`AppState`].
+///
+/// The widget advances the animation itself: on every animation frame it
+/// moves its data forward by the real elapsed time and requests the next
+/// frame. As it passes the stages of each beat, it submits [`BEAT_EVENT`]
+/// notifications and calls the callbacks registered with
+/// [`HeartBuilder::on_beat`].
+///
+/// The heart also responds to the mouse. Hovering over its outline, not just
+/// its bounding box, highlights it, and clicking it performs its
+/// [`ClickAction`], submits a [`CLICKED`] notification and calls the
+/// callbacks registered with [`HeartBuilder::on_click`].
+///
+/// Colors and the outline width not set on the builder follow the keys of
+/// [`theme`](crate::theme) wherever the [`Env`] sets them.
+pub struct HeartWidget {
+    /// Preferred size; `None` fills the available space.
+    size: Option<Size>,
+    painter: HeartPainter,
+    themed: Themed,
+    tracker: BeatTracker,
+    callbacks: Vec<BeatCallback>,
+    hover_color: Option<Color>,
+    click_action: ClickAction,
+    click_callbacks: Vec<ClickCallback>,
+    /// The last position of the mouse over the widget.
+    mouse: Option<Point>,
+    /// Whether the mouse is over the heart.
+    hovered: bool,
+    /// How far the heart is squeezed in, from 0.0 to 1.0.
+    squeeze: f64,
+}
+
+/// Configures a [`HeartWidget`] and the [`AppState`] it starts from.
+#[derive(Clone)]
+pub struct HeartBuilder {
+    size: Option<Size>,
+    painter: HeartPainter,
+    themed: Themed,
+    bpm: f64,
+    amplitude: f64,
+    callbacks: Vec<BeatCallback>,
+    hover_color: Option<Color>,
+    click_action: ClickAction,
+    click_callbacks: Vec<ClickCallback>,
+}
+
+impl HeartWidget {
+    /// Creates a red heart with a black outline that fills the available
+    /// space and beats with the default envelope.
+    pub fn new() -> Self {
+        HeartWidget::builder().build()
+    }
+
+    /// Returns a builder for configuring a heart.
+    pub fn builder() -> HeartBuilder {
+        HeartBuilder::default()
+    }
+
+    /// Creates a heart drawn by `painter` that fills the available space,
+    /// with the parts `themed` names following the [`Env`]'s theme.
+    pub(crate) fn from_painter(painter: HeartPainter, themed: Themed) -> Self {
+        HeartBuilder {
+            painter,
+            themed,
+            ..HeartBuilder::default()
+        }
+        .build()
+    }
+
+    /// Changes the color the heart is filled with, regardless of the theme.
+    pub(crate) fn set_fill(&mut self, fill: Color) {
+        self.painter.fill = fill;
+        self.themed.fill = false;
+    }
+
+    /// Returns the heart's outline as currently painted, including any
+    /// squeeze.
+    fn outline(&self, size: Size, data: &AppState) -> BezPath {
+        // Ease in and out of the squeeze
+        let squeeze = self.squeeze * self.squeeze * (3.0 - 2.0 * self.squeeze);
+        let scale = self.painter.scale(data) * (1.0 - SQUEEZE_DEPTH * squeeze);
+        self.painter.path_at_scale(size, scale)
+    }
+
+    /// Returns whether a point lies inside the heart's outline.
+    fn hit(&self, ctx: &EventCtx, data: &AppState, point: Point) -> bool {
+        self.outline(ctx.size(), data).contains(point)
+    }
+
+    /// Updates the hover highlight and the mouse cursor.
+    fn set_hovered(&mut self, ctx: &mut EventCtx, hovered: bool) {
+        if hovered {
+            ctx.set_cursor(&Cursor::Pointer);
+        } else {
+            ctx.clear_cursor();
+        }
+        if self.hovered != hovered {
+            self.hovered = hovered;
+            ctx.request_paint();
+        }
+    }
+
+    /// Performs the click action and reports the click.
+    fn click(&mut self, ctx: &mut EventCtx, data: &mut AppState) {
+        if self.click_action == ClickAction::Beat {
+            data.sync_beat(envelope::peak_phase(&*self.painter.envelope));
+        }
+        for callback in &self.click_callbacks {
+            callback(data);
+        }
+        ctx.submit_notification_without_warning(CLICKED);
+        ctx.request_paint();
+    }
+}
+
+impl Default for HeartWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeartBuilder {
+    /// Sets the widget's preferred size. Without one, the widget fills the
+    /// space its parent allows.
+    pub fn size(mut self, size: impl Into<Size>) -> Self {
+        self.size = Some(size.into());
+        self
+    }
+
+    /// Sets the color the widget's background is filled with. Without one,
+    /// the background is left to the parent.
+    pub fn background(mut self, color: Color) -> Self {
+        self.painter.background = Some(color);
+        self.themed.background = false;
+        self
+    }
+
+    /// Sets the color the heart is filled with.
+    pub fn fill(mut self, color: Color) -> Self {
+        self.painter.fill = color;
+        self.themed.fill = false;
+        self
+    }
+
+    /// Sets the colors the fill passes through over each beat, e.g.
+    /// [`ColorRamp::Flush`] to darken it at rest and brighten it as the
+    /// heart contracts.
+    pub fn color_ramp(mut self, ramp: ColorRamp) -> Self {
+        self.painter.color_ramp = Some(ramp);
+        self
+    }
+
+    /// Sets the color of the heart's outline.
+    pub fn stroke(mut self, color: Color) -> Self {
+        self.painter.stroke = color;
+        self.themed.stroke = false;
+        self
+    }
+
+    /// Sets the width of the heart's outline; zero disables it.
+    pub fn stroke_width(mut self, width: f64) -> Self {
+        self.painter.stroke_width = width.max(0.0);
+        self.themed.stroke_width = false;
+        self
+    }
+
+    /// Sets how the outline follows the heart's edge: its alignment, joins,
+    /// caps and dashes.
+    pub fn stroke_options(mut self, options: StrokeOptions) -> Self {
+        self.painter.stroke_options = options;
+        self
+    }
+
+    /// Sets the order in which the fill and the outline are painted.
+    /// Defaults to [`PaintOrder::FillFirst`], which shows the whole
+    /// outline.
+    pub fn paint_order(mut self, order: PaintOrder) -> Self {
+        self.painter.paint_order = order;
+        self
+    }
+
+    /// Draws the heart in the colors of a built-in theme wherever the
+    /// [`Env`] sets no theme of its own. Colors set explicitly, before or
+    /// after, still take precedence.
+    pub fn theme(mut self, theme: Theme) -> Self {
+        if self.themed.fill {
+            self.painter.fill = theme.fill();
+        }
+        if self.themed.stroke {
+            self.painter.stroke = theme.stroke();
+        }
+        if self.themed.stroke_width {
+            self.painter.stroke_width = theme.stroke_width();
+        }
+        if self.themed.background {
+            self.painter.background = Some(theme.background());
+        }
+        self
+    }
+
+    /// Sets the outline the heart pulses with: a [`HeartShape`] with its
+    /// parameters or any other [`Icon`].
+    ///
+    /// [`HeartShape`]: crate::HeartShape
+    pub fn shape(mut self, shape: impl Into<Icon>) -> Self {
+        self.painter.shape = shape.into();
+        self
+    }
+
+    /// Sets the gradient, shadow, glow and highlight drawn with the heart.
+    pub fn style(mut self, style: HeartStyle) -> Self {
+        self.painter.style = style;
+        self
+    }
+
+    /// Sets the envelope shaping each beat.
+    pub fn envelope(mut self, envelope: impl BeatEnvelope + 'static) -> Self {
+        self.painter.envelope = Arc::new(envelope);
+        self
+    }
+
+    /// Sets the initial heart rate in beats per minute.
+    pub fn bpm(mut self, bpm: f64) -> Self {
+        self.bpm = beat::clamp_bpm(bpm);
+        self
+    }
+
+    /// Sets the initial relative size change at the peak of a beat.
+    pub fn amplitude(mut self, amplitude: f64) -> Self {
+        self.amplitude = beat::clamp_amplitude(amplitude);
+        self
+    }
+
+    /// Registers a function to call whenever the heart passes a stage of a
+    /// beat, e.g. to flash, count beats or trigger sounds. Callbacks run on
+    /// the UI thread, in the order they were registered.
+    pub fn on_beat(mut self, callback: impl Fn(&BeatEvent) + 'static) -> Self {
+        self.callbacks.push(Rc::new(callback));
+        self
+    }
+
+    /// Sets the color painted over the heart while the mouse hovers over
+    /// it; `None` disables the highlight. Defaults to
+    /// [`DEFAULT_HOVER_COLOR`].
+    pub fn hover_color(mut self, color: Option<Color>) -> Self {
+        self.hover_color = color;
+        self
+    }
+
+    /// Sets what clicking the heart does. Defaults to [`ClickAction::Beat`].
+    pub fn click_action(mut self, action: ClickAction) -> Self {
+        self.click_action = action;
+        self
+    }
+
+    /// Registers a function to call whenever the heart is clicked, e.g. to
+    /// use it as a button. Callbacks run on the UI thread after the click
+    /// action, in the order they were registered, and may change the state.
+    pub fn on_click(mut self, callback: impl Fn(&mut AppState) + 'static) -> Self {
+        self.click_callbacks.push(Rc::new(callback));
+        self
+    }
+
+    /// Returns a painter drawing the configured heart, for rendering it
+    /// outside a window.
+    pub fn painter(&self) -> HeartPainter {
+        self.painter.clone()
+    }
+
+    /// Returns which parts of the painter follow the [`Env`]'s theme: those
+    /// not set explicitly.
+    pub fn themed(&self) -> Themed {
+        self.themed
+    }
+
+    /// Returns the state the configured heart starts from.
+    pub fn state(&self) -> AppState {
+        AppState::new(self.bpm, self.amplitude)
+    }
+
+    /// Creates the configured widget.
+    pub fn build(self) -> HeartWidget {
+        HeartWidget {
+            size: self.size,
+            tracker: BeatTracker::new(&*self.painter.envelope),
+            painter: self.painter,
+            themed: self.themed,
+            callbacks: self.callbacks,
+            hover_color: self.hover_color,
+            click_action: self.click_action,
+            click_callbacks: self.click_callbacks,
+            mouse: None,
+            hovered: false,
+            squeeze: 0.0,
+        }
+    }
+}
+
+impl Default for HeartBuilder {
+    fn default() -> Self {
+        HeartBuilder {
+            size: None,
+            painter: HeartPainter::default(),
+            themed: Themed::default(),
+            bpm: beat::DEFAULT_BPM,
+            amplitude: beat::DEFAULT_AMPLITUDE,
+            callbacks: Vec::new(),
+            hover_color: Some(DEFAULT_HOVER_COLOR),
+            click_action: ClickAction::default(),
+            click_callbacks: Vec::new(),
+        }
+    }
+}
+
+impl Widget<AppState> for HeartWidget {
+    /// Handles events for the HeartWidget.
+    ///
+    /// In particular, it processes animation frame events to advance the
+    /// animation by the real elapsed interval and request the next
+    /// animation frame and repaint, [`SET_PAINTER`] and [`SET_FILL`]
+    /// commands to change how the heart is drawn, [`SET_BPM`] and
+    /// [`SET_AMPLITUDE`] commands to change its beat and [`BEAT`] commands
+    /// to contract right away. Mouse events are tested against the heart's
+    /// outline to highlight it and to detect clicks. Afterwards it reports
+    /// the beat stages passed through [`BEAT_EVENT`] notifications and the
+    /// beat callbacks.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The event context used to request animation frames and painting.
+    /// * `event` - The event being handled. Only `AnimFrame` events, mouse
+    ///   events and [`SET_PAINTER`], [`SET_FILL`], [`SET_BPM`],
+    ///   [`SET_AMPLITUDE`] and [`BEAT`] commands are processed.
+    /// * `data` - The application state, which holds the animation model.
+    /// * `_env` - The environment, which is currently unused.
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut AppState, _env: &Env) {
+        match event {
+            Event::AnimFrame(interval) => {
+                // Advance the animation by the real elapsed interval
+                data.advance(*interval);
+
+                // Press the squeeze in while the heart is held, release it otherwise
+                let step = *interval as f64 * 1e-9 / SQUEEZE_TIME;
+                let pressed = ctx.is_active() && self.click_action == ClickAction::Squeeze;
+                self.squeeze = if pressed {
+                    (self.squeeze + step).min(1.0)
+                } else {
+                    (self.squeeze - step).max(0.0)
+                };
+
+                // The heart moves under a resting mouse, too
+                if let Some(pos) = self.mouse {
+                    let hovered = self.hit(ctx, data, pos);
+                    self.set_hovered(ctx, hovered);
+                }
+
+                // Request the next animation frame
+                ctx.request_anim_frame();
+
+                // Request a repaint to update the display
+                ctx.request_paint();
+            }
+            Event::Command(cmd) => {
+                if let Some((painter, themed)) = cmd.get(SET_PAINTER) {
+                    self.painter = painter.clone();
+                    self.themed = *themed;
+                    self.tracker.set_envelope(&*painter.envelope);
+                    ctx.request_paint();
+                } else if let Some(fill) = cmd.get(SET_FILL) {
+                    self.set_fill(*fill);
+                    ctx.request_paint();
+                } else if let Some(bpm) = cmd.get(SET_BPM) {
+                    data.bpm = *bpm;
+                } else if let Some(amplitude) = cmd.get(SET_AMPLITUDE) {
+                    data.amplitude = *amplitude;
+                } else if cmd.is(BEAT) {
+                    data.sync_beat(envelope::peak_phase(&*self.painter.envelope));
+                    ctx.request_paint();
+                }
+            }
+            Event::MouseMove(mouse) => {
+                self.mouse = Some(mouse.pos);
+                let hovered = self.hit(ctx, data, mouse.pos);
+                self.set_hovered(ctx, hovered);
+                return;
+            }
+            Event::MouseDown(mouse) if mouse.button.is_left() => {
+                if self.hit(ctx, data, mouse.pos) {
+                    ctx.set_active(true);
+                    ctx.set_handled();
+                }
+                return;
+            }
+            Event::MouseUp(mouse) if mouse.button.is_left() && ctx.is_active() => {
+                ctx.set_active(false);
+                ctx.set_handled();
+                if self.hit(ctx, data, mouse.pos) {
+                    self.click(ctx, data);
+                }
+            }
+            _ => return,
+        }
+
+        for beat_event in self.tracker.update(data) {
+            for callback in &self.callbacks {
+                callback(&beat_event);
+            }
+            ctx.submit_notification_without_warning(BEAT_EVENT.with(beat_event));
+        }
+    }
+
+    /// Handles life cycle events for the HeartWidget.
+    ///
+    /// In particular, it handles the `WidgetAdded` event by requesting
+    /// an animation frame to start the animation loop, and drops the hover
+    /// highlight when the mouse leaves the widget.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The lifecycle context used to request animation frames.
+    /// * `event` - The lifecycle event being handled.
+    /// * `_data` - The application state, which is currently unused.
+    /// * `_env` - The environment, which is currently unused.
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        _data: &AppState,
+        _env: &Env,
+    ) {
+        match event {
+            LifeCycle::WidgetAdded => {
+                // Start the animation loop
+                ctx.request_anim_frame();
+            }
+            LifeCycle::HotChanged(false) => {
+                self.mouse = None;
+                if self.hovered {
+                    self.hovered = false;
+                    ctx.request_paint();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Repaints the heart when the theme in the environment changes.
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &AppState, _data: &AppState, _env: &Env) {
+        if ctx.env_key_changed(&theme::FILL)
+            || ctx.env_key_changed(&theme::STROKE)
+            || ctx.env_key_changed(&theme::STROKE_WIDTH)
+            || ctx.env_key_changed(&theme::BACKGROUND)
+        {
+            ctx.request_paint();
+        }
+    }
+
+    /// Computes the preferred size of the HeartWidget.
+    ///
+    /// The preferred size is the configured size, constrained to the
+    /// `BoxConstraints` passed in. Without a configured size it is the
+    /// maximum size allowed, which makes the widget fill its parent.
+    ///
+    /// # Arguments
+    ///
+    /// * `_ctx` - The layout context, which is currently unused.
+    /// * `bc` - The box constraints that specify the allowed sizes.
+    /// * `_data` - The application state, which is currently unused.
+    /// * `_env` - The environment, which is currently unused.
+    ///
+    /// # Returns
+    ///
+    /// The preferred size of the widget.
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &AppState,
+        _env: &Env,
+    ) -> Size {
+        match self.size {
+            Some(size) => bc.constrain(size),
+            None => bc.max(), // Make the widget fill its parent
+        }
+    }
+
+    /// Paints a heart shape on the widget with a beating animation effect.
+    ///
+    /// The heart shape is drawn centered within the widget, and its size
+    /// follows the widget's beat envelope once per beat to simulate a beating
+    /// effect. The heart is outlined and filled with the configured colors,
+    /// or the theme's where none were configured, and highlighted while the
+    /// mouse hovers over it.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The painting context used to draw the heart.
+    /// * `data` - The application state, which provides the beat phase and
+    ///   amplitude for the beating animation.
+    /// * `env` - The environment, which may set the theme's colors.
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppState, env: &Env) {
+        let size = ctx.size();
+        let path = self.outline(size, data);
+        let painter = self.themed.resolve(&self.painter, env);
+        painter.paint_path(ctx.render_ctx, size, &path, painter.pulse(data));
+        if let (true, Some(color)) = (self.hovered, self.hover_color) {
+            // Outline-only icons are highlighted along their outline
+            if painter.shape.is_filled() {
+                ctx.fill(&path, &color);
+            } else if painter.stroke_width > 0.0 {
+                let style = painter.stroke_options.stroke_style();
+                ctx.stroke_styled(&path, &color, painter.stroke_width, &style);
+            }
+        }
+    }
+}